@@ -0,0 +1,335 @@
+//! Baseline timings for the ping output parser and the lobby handling, run with
+//! `cargo bench`. Reading big lobbies also prints the most memory it took, which
+//! criterion doesn't measure.
+//!
+//! Pinging a big lobby is measured against the two other ways it could be done: a
+//! future per room all joined at once, as it used to be, and a pool of `concurrency`
+//! workers taking rooms off a channel. On 5000 rooms all three take as long, and at most
+//! about 3.4 MiB streamed, 4.6 MiB joined and 5.3 MiB pooled, the rooms included.
+//! [`ping_rooms`] already only holds `concurrency` pings at a time, and the pool would
+//! need a `'static` pinger to spawn the workers with on top of that, so it stays
+//! streamed.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex, Semaphore};
+use tokio_util::sync::CancellationToken;
+use yuzuping::{
+    ping_rooms, read_lobby, read_lobby_filtered, Error, LobbySchema, PingConfig, PingStats, Pinger,
+    Player, ProbeResult, Replies, Response, Room,
+};
+
+/// Keeps count of the bytes allocated, and the most that were at once.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+// SAFETY: everything is left to the system allocator, only the sizes are counted
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+
+const LINUX: &str = "\
+PING 203.0.113.7 (203.0.113.7) 56(84) bytes of data.
+64 bytes from 203.0.113.7: icmp_seq=1 ttl=54 time=12.4 ms
+64 bytes from 203.0.113.7: icmp_seq=2 ttl=54 time=11.9 ms
+64 bytes from 203.0.113.7: icmp_seq=3 ttl=54 time=13.1 ms
+
+--- 203.0.113.7 ping statistics ---
+3 packets transmitted, 3 received, 0% packet loss, time 2003ms
+rtt min/avg/max/mdev = 11.912/12.466/13.104/0.487 ms
+";
+
+const WINDOWS: &str = "\r
+Pinging 203.0.113.7 with 32 bytes of data:\r
+Reply from 203.0.113.7: bytes=32 time=12ms TTL=54\r
+Reply from 203.0.113.7: bytes=32 time=11ms TTL=54\r
+Reply from 203.0.113.7: bytes=32 time<1ms TTL=54\r
+\r
+Ping statistics for 203.0.113.7:\r
+    Packets: Sent = 3, Received = 3, Lost = 0 (0% loss),\r
+Approximate round trip times in milli-seconds:\r
+    Minimum = 0ms, Maximum = 12ms, Average = 7ms\r
+";
+
+const MACOS: &str = "\
+PING 203.0.113.7 (203.0.113.7): 56 data bytes
+64 bytes from 203.0.113.7: icmp_seq=0 ttl=54 time=12.412 ms
+64 bytes from 203.0.113.7: icmp_seq=1 ttl=54 time=11.903 ms
+Request timeout for icmp_seq 2
+
+--- 203.0.113.7 ping statistics ---
+3 packets transmitted, 2 packets received, 33.3% packet loss
+round-trip min/avg/max/stddev = 11.903/12.158/12.412/0.255 ms
+";
+
+/// Call `f` once and print the most memory it had allocated at once, including what it
+/// returned.
+fn peak_memory<T>(name: &str, f: impl FnOnce() -> T) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    let result = black_box(f());
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+    drop(result);
+    println!("{name:<32} {:>9} KiB at most", peak / 1024);
+}
+
+/// A transcript of `count` linux replies, for how the parser copes with long output.
+fn long_linux(count: usize) -> String {
+    let mut stdout = String::from("PING 203.0.113.7 (203.0.113.7) 56(84) bytes of data.\n");
+    for seq in 1..=count {
+        stdout += &format!("64 bytes from 203.0.113.7: icmp_seq={seq} ttl=54 time=12.4 ms\n");
+    }
+    stdout += &format!(
+        "\n--- 203.0.113.7 ping statistics ---\n\
+         {count} packets transmitted, {count} received, 0% packet loss, time 2003ms\n"
+    );
+    stdout
+}
+
+/// A lobby of `count` rooms spread over a handful of games, with some listed twice.
+fn lobby(count: usize) -> Response {
+    let games = [
+        "Super Smash Bros. Ultimate",
+        "Mario Kart 8 Deluxe",
+        "Splatoon 3",
+        "Pokémon Scarlet",
+        "Animal Crossing: New Horizons",
+    ];
+
+    let rooms = (0..count)
+        .map(|i| {
+            // every tenth room is a duplicate of the one before it
+            let host = if i % 10 == 9 { i - 1 } else { i };
+            (i, host)
+        })
+        .map(|(i, host)| Room {
+            port: 24872,
+            name: format!("Room {i}"),
+            game_name: games[i % games.len()].to_string(),
+            address: format!("10.0.{}.{}", host / 256, host % 256),
+            players: (0..i % 8)
+                .map(|player| Player {
+                    nickname: format!("player{player}"),
+                    game: games[i % games.len()].to_string(),
+                })
+                .collect(),
+            max_players: Some(8),
+            ..Room::default()
+        })
+        .collect();
+
+    Response { rooms }
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, stdout) in [("linux", LINUX), ("windows", WINDOWS), ("macos", MACOS)] {
+        group.bench_function(name, |b| {
+            b.iter(|| Replies::from_ping_output(&IP, black_box(stdout.as_bytes()), 3))
+        });
+    }
+    for count in [10, 100, 1000] {
+        let stdout = long_linux(count);
+        group.bench_with_input(
+            BenchmarkId::new("linux replies", count),
+            &stdout,
+            |b, stdout| {
+                b.iter(|| Replies::from_ping_output(&IP, black_box(stdout.as_bytes()), u8::MAX))
+            },
+        );
+    }
+    group.finish();
+}
+
+// what happens to a lobby between fetching and pinging it
+fn filter_and_sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter and sort");
+    for count in [100, 5000] {
+        let resp = lobby(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &resp, |b, resp| {
+            b.iter(|| {
+                let mut resp = Response {
+                    rooms: resp.rooms.clone(),
+                };
+                resp.dedup();
+                let game = match resp.find_game("smash") {
+                    yuzuping::GameMatch::Found(game) => game,
+                    _ => unreachable!("the lobby hosts smash"),
+                };
+                resp.rooms.retain(|room| room.game_name == game);
+                resp.rooms
+                    .sort_by_key(|room| std::cmp::Reverse(room.players.len()));
+                resp.rooms
+            })
+        });
+    }
+    group.finish();
+}
+
+// a big lobby of which only the one game is kept, read in whole or room by room
+fn read(c: &mut Criterion) {
+    let path = std::env::temp_dir().join("yuzuping-bench-lobby.json");
+    let json = serde_json::to_vec(&lobby(50_000)).expect("the lobby is serializable");
+    std::fs::write(&path, json).expect("the lobby can be saved");
+    let game = "Super Smash Bros. Ultimate";
+    let then_filter = || {
+        let mut resp = read_lobby(&path, LobbySchema::Yuzu).unwrap();
+        resp.rooms.retain(|room| room.game_name == game);
+        resp
+    };
+    let filtering =
+        || read_lobby_filtered(&path, LobbySchema::Yuzu, |room| room.game_name == game).unwrap();
+
+    peak_memory("read 50000 rooms, then filter", then_filter);
+    peak_memory("read 50000 rooms, filtering", filtering);
+
+    let mut group = c.benchmark_group("read 50000 rooms");
+    group.sample_size(10);
+    group.bench_function("then filter", |b| b.iter(then_filter));
+    group.bench_function("filtering", |b| b.iter(filtering));
+    group.finish();
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Replies after a millisecond, every time.
+struct Instant;
+
+impl Pinger for Instant {
+    async fn ping(&self, _: &IpAddr, _: u16) -> Result<Replies, Error> {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        Ok(Replies::from_probes(vec![Duration::from_millis(1)], 1))
+    }
+}
+
+const CONCURRENCY: usize = 100;
+
+fn pinged(replies: Result<Replies, Error>) -> ProbeResult {
+    match replies
+        .ok()
+        .and_then(|replies| PingStats::from_replies(&replies.latencies))
+    {
+        Some(stats) => ProbeResult::Reachable(stats),
+        None => ProbeResult::Timeout,
+    }
+}
+
+async fn streamed(mut rooms: Vec<Room>) -> Vec<Room> {
+    let config = PingConfig::builder().concurrency(CONCURRENCY).build();
+    ping_rooms(
+        &mut rooms,
+        &config,
+        &Instant,
+        &CancellationToken::new(),
+        |_, _, _| {},
+    )
+    .await
+    .unwrap();
+    rooms
+}
+
+async fn joined(mut rooms: Vec<Room>) -> Vec<Room> {
+    let permits = Semaphore::new(CONCURRENCY);
+    futures::future::join_all(rooms.iter_mut().map(|room| async {
+        let _permit = permits.acquire().await.unwrap();
+        let ip = room.ip.unwrap();
+        room.ping = pinged(Instant.ping(&ip, 24872).await);
+    }))
+    .await;
+    rooms
+}
+
+async fn pooled(rooms: Vec<Room>) -> Vec<Room> {
+    let total = rooms.len();
+    let (to_ping, queue) = mpsc::channel(CONCURRENCY);
+    let (pinged_tx, mut pinged_rx) = mpsc::channel(CONCURRENCY);
+    let queue = Arc::new(Mutex::new(queue));
+    let pinger = Arc::new(Instant);
+    for _ in 0..CONCURRENCY {
+        let (queue, pinged_tx, pinger) = (queue.clone(), pinged_tx.clone(), pinger.clone());
+        tokio::spawn(async move {
+            loop {
+                let Some(mut room): Option<Room> = queue.lock().await.recv().await else {
+                    return;
+                };
+                let ip = room.ip.unwrap();
+                room.ping = pinged(pinger.ping(&ip, 24872).await);
+                if pinged_tx.send(room).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    drop(pinged_tx);
+    tokio::spawn(async move {
+        for room in rooms {
+            if to_ping.send(room).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut done = Vec::with_capacity(total);
+    while let Some(room) = pinged_rx.recv().await {
+        done.push(room);
+    }
+    done
+}
+
+// how it's pinged, against the ways it could have been
+fn ping(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("a runtime can be started");
+    let mut rooms = lobby(5000).rooms;
+    for room in &mut rooms {
+        room.ip = room.address.parse().ok();
+    }
+
+    peak_memory("ping 5000 rooms, streamed", || {
+        runtime.block_on(streamed(rooms.clone()))
+    });
+    peak_memory("ping 5000 rooms, joined", || {
+        runtime.block_on(joined(rooms.clone()))
+    });
+    peak_memory("ping 5000 rooms, pooled", || {
+        runtime.block_on(pooled(rooms.clone()))
+    });
+
+    let mut group = c.benchmark_group("ping 5000 rooms");
+    group.sample_size(10);
+    group.bench_function("streamed", |b| {
+        b.iter(|| runtime.block_on(streamed(rooms.clone())))
+    });
+    group.bench_function("joined", |b| {
+        b.iter(|| runtime.block_on(joined(rooms.clone())))
+    });
+    group.bench_function("pooled", |b| {
+        b.iter(|| runtime.block_on(pooled(rooms.clone())))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, filter_and_sort, read, ping);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use std::net::IpAddr;
+
+/// Clean up an address as listed by the lobby, returning it along with the port it
+/// carried if any.
+///
+/// Surrounding whitespace, a `tcp://` style scheme and anything after a `/` are removed,
+/// and a trailing `:port` (or `[v6]:port`) is split off. What's left has to be an IP or
+/// a hostname, so that nothing that looks like an option ever reaches `ping`.
+pub(crate) fn normalize(address: &str) -> Result<(String, Option<u16>), &'static str> {
+    let mut address = address.trim();
+
+    if let Some((_, rest)) = address.split_once("://") {
+        address = rest;
+    }
+    if let Some((host, _)) = address.split_once('/') {
+        address = host;
+    }
+
+    let (host, port) = split_port(address)?;
+
+    if host.is_empty() {
+        return Err("the address is empty");
+    }
+    if host.parse::<IpAddr>().is_err() && !is_hostname(host) {
+        return Err("the address is neither an IP nor a hostname");
+    }
+
+    Ok((host.to_string(), port))
+}
+
+fn split_port(address: &str) -> Result<(&str, Option<u16>), &'static str> {
+    let parse_port = |port: &str| port.parse().map_err(|_| "the port isn't a number");
+
+    // [::1]:24872
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or("the [ is never closed")?;
+        return match rest.strip_prefix(':') {
+            Some(port) => Ok((host, Some(parse_port(port)?))),
+            None if rest.is_empty() => Ok((host, None)),
+            None => Err("there's garbage after the ]"),
+        };
+    }
+
+    // a bare IPv6 address has more than one colon, and no port
+    match address.split_once(':') {
+        Some((host, port)) if !port.contains(':') => Ok((host, Some(parse_port(port)?))),
+        _ => Ok((address, None)),
+    }
+}
+
+fn is_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+
+    host.len() <= 253
+        && host.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
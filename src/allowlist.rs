@@ -0,0 +1,47 @@
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Addresses and networks to skip rooms by or to keep only, read from a file with one
+/// per line. Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Default)]
+pub struct AddressList {
+    hosts: Vec<String>,
+    networks: Vec<IpNet>,
+}
+
+impl AddressList {
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let mut list = AddressList::default();
+        for line in std::fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Ok(network) = line.parse::<IpNet>() {
+                list.networks.push(network);
+            } else if let Ok(ip) = line.parse::<IpAddr>() {
+                list.networks.push(ip.into());
+            } else {
+                list.hosts.push(line.to_string());
+            }
+        }
+        Ok(list)
+    }
+
+    /// Whether matching takes the IP the room's address resolves to.
+    pub fn has_networks(&self) -> bool {
+        !self.networks.is_empty()
+    }
+
+    /// Whether the room at `address`, which resolved to `ip` if it was looked up, is on
+    /// the list.
+    pub fn contains(&self, address: &str, ip: Option<IpAddr>) -> bool {
+        let address = address.trim();
+        self.hosts
+            .iter()
+            .any(|host| host.eq_ignore_ascii_case(address))
+            || ip.is_some_and(|ip| self.networks.iter().any(|network| network.contains(&ip)))
+    }
+}
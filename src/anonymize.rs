@@ -0,0 +1,23 @@
+use std::hash::{BuildHasher, RandomState};
+use yuzuping::Room;
+
+/// Stands in for room names and player nicknames with placeholders like `room-9c1d2e`,
+/// for sharing the output without them. A name gets the same placeholder throughout a
+/// run, and a different one the next, so that they can't be looked up.
+#[derive(Debug, Default)]
+pub struct Anonymizer(RandomState);
+
+impl Anonymizer {
+    pub fn apply(&self, rooms: &mut [Room]) {
+        for room in rooms {
+            room.name = self.placeholder("room", &room.name);
+            for player in &mut room.players {
+                player.nickname = self.placeholder("player", &player.nickname);
+            }
+        }
+    }
+
+    fn placeholder(&self, kind: &str, name: &str) -> String {
+        format!("{kind}-{:06x}", self.0.hash_one(name) & 0xff_ffff)
+    }
+}
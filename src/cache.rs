@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use yuzuping::{Response, Room};
+
+/// The rooms of the previous run, to tell what changed since.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Cache {
+    /// The games that were being looked at. Rooms of other games weren't cached, so they
+    /// can't be told apart from new ones.
+    games: Vec<String>,
+    rooms: Vec<CachedRoom>,
+}
+
+// `Room` skips the ping when (de)serializing since it isn't part of the lobby schema
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedRoom {
+    name: String,
+    game: String,
+    address: String,
+    port: u32,
+    players: usize,
+    ping: Option<Duration>,
+}
+
+/// How the rooms differ from the cached ones.
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// What changed about each room, keyed by address and port. Rooms that stayed the
+    /// same are left out.
+    pub rooms: HashMap<(String, u32), String>,
+    /// The names of the rooms that are no longer in the lobby.
+    pub gone: Vec<String>,
+}
+
+impl Changes {
+    pub fn of(&self, room: &Room) -> Option<&str> {
+        self.rooms
+            .get(&(room.address.clone(), room.port))
+            .map(String::as_str)
+    }
+}
+
+impl Cache {
+    pub fn new(games: &[String], rooms: &[Room]) -> Self {
+        Cache {
+            games: games.to_vec(),
+            rooms: rooms
+                .iter()
+                .map(|room| CachedRoom {
+                    name: room.name.clone(),
+                    game: room.game_name.clone(),
+                    address: room.address.clone(),
+                    port: room.port,
+                    players: room.players.len(),
+                    ping: room.ping.stats().map(|ping| ping.min),
+                })
+                .collect(),
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Some(dir()?.join("last.json"))
+    }
+
+    /// The cache of the previous run, if there was one. A cache that can't be read is
+    /// as good as none, it'll be overwritten by this run.
+    pub fn load() -> Option<Self> {
+        let json = std::fs::read(Self::path()?).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write(Self::path(), &serde_json::to_vec(self)?)
+    }
+
+    /// Compare `rooms`, which host `games`, against the cached ones. Changes in ping are
+    /// only included with `with_ping`, since they'd otherwise show up for nearly every
+    /// room.
+    pub fn compare(&self, games: &[String], rooms: &[Room], with_ping: bool) -> Changes {
+        let cached = self
+            .rooms
+            .iter()
+            .map(|room| ((room.address.as_str(), room.port), room))
+            .collect::<HashMap<_, _>>();
+
+        let mut changes = Changes::default();
+
+        for room in rooms {
+            if !self.games.contains(&room.game_name) {
+                continue;
+            }
+
+            let Some(before) = cached.get(&(room.address.as_str(), room.port)) else {
+                changes
+                    .rooms
+                    .insert((room.address.clone(), room.port), "NEW".to_string());
+                continue;
+            };
+
+            let mut change = Vec::new();
+
+            let joined = room.players.len() as isize - before.players as isize;
+            match joined {
+                0 => {}
+                1 | -1 => change.push(format!("{joined:+} player")),
+                _ => change.push(format!("{joined:+} players")),
+            }
+
+            if with_ping {
+                if let (Some(now), Some(then)) = (room.ping.stats(), before.ping) {
+                    let diff = now.min.as_millis() as i128 - then.as_millis() as i128;
+                    if diff != 0 {
+                        change.push(format!("{diff:+}ms"));
+                    }
+                }
+            }
+
+            if !change.is_empty() {
+                changes
+                    .rooms
+                    .insert((room.address.clone(), room.port), change.join(" "));
+            }
+        }
+
+        let present = rooms
+            .iter()
+            .map(|room| (room.address.as_str(), room.port))
+            .collect::<Vec<_>>();
+        changes.gone = self
+            .rooms
+            .iter()
+            .filter(|room| games.contains(&room.game))
+            .filter(|room| !present.contains(&(room.address.as_str(), room.port)))
+            .map(|room| room.name.clone())
+            .collect();
+
+        changes
+    }
+}
+
+/// The lobby as it was last fetched, so that running again straight away doesn't fetch
+/// it again.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LobbyCache {
+    /// The lobbies it was fetched from, it says nothing about any others
+    urls: Vec<String>,
+    fetched_at: SystemTime,
+    lobby: Response,
+}
+
+impl LobbyCache {
+    pub fn new(urls: &[String], fetched_at: SystemTime, lobby: Response) -> Self {
+        LobbyCache {
+            urls: urls.to_vec(),
+            fetched_at,
+            lobby,
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Some(dir()?.join("lobby.json"))
+    }
+
+    /// The lobby fetched from `urls` less than `ttl` ago, if there is one.
+    pub fn load(urls: &[String], ttl: Duration) -> Option<Self> {
+        let json = std::fs::read(Self::path()?).ok()?;
+        let cache = serde_json::from_slice::<LobbyCache>(&json).ok()?;
+
+        // a time in the future is from a clock that was changed since, and can't be
+        // trusted either way
+        let age = cache.fetched_at.elapsed().ok()?;
+        (cache.urls == urls && age < ttl).then_some(cache)
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write(Self::path(), &serde_json::to_vec(self)?)
+    }
+
+    /// The lobby, and when it was fetched.
+    pub fn into_lobby(self) -> (Response, SystemTime) {
+        (self.lobby, self.fetched_at)
+    }
+}
+
+fn dir() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("yuzuping"))
+}
+
+fn write(path: Option<PathBuf>, json: &[u8]) -> std::io::Result<()> {
+    let path = path
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no cache directory"))?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, json)
+}
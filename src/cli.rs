@@ -0,0 +1,839 @@
+use crate::output::JSON_FIELDS;
+use crate::template::{Field, StatusField, Template};
+use clap::builder::{PossibleValuesParser, RangedU64ValueParser};
+use clap::{value_parser, ArgGroup, Parser, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+use yuzuping::{LobbySchema, PingCommand, PingConfig, PingStats, Probe};
+
+/// Lists the rooms of the yuzu lobby, closest first.
+///
+/// The options with an environment variable listed next to them, like the lobby url and
+/// the ping count, can also be set through it.
+///
+/// Exits with 3 when the lobby can't be fetched, 4 when no rooms match the filters and 5
+/// when none of them are reachable.
+#[derive(Parser, Debug)]
+#[command(version, about, group(ArgGroup::new("refreshing").args(["watch", "loop_every"])))]
+pub struct Args {
+    /// Lobby API to fetch the rooms from, can be repeated or comma separated to combine
+    /// the rooms of several lobbies
+    #[arg(
+        long,
+        env = "YUZU_LOBBY_URL",
+        value_delimiter = ',',
+        default_value = "https://api.yuzu-emu.org/lobby"
+    )]
+    pub url: Vec<String>,
+
+    /// Only show rooms hosting this game, can be repeated or comma separated to show
+    /// several games at once. Without it, asks which of the hosted games to show when
+    /// run in a terminal and shows Super Smash Bros. Ultimate otherwise. A blank game
+    /// shows every game
+    #[arg(long, env = "YUZU_GAME_NAME", value_delimiter = ',')]
+    pub game: Vec<String>,
+
+    /// Which emulator's lobby API the --url speaks, also used for --from-file
+    #[arg(long, env = "YUZU_LOBBY_SCHEMA", value_enum, default_value_t = SchemaArg::Yuzu)]
+    pub schema: SchemaArg,
+
+    /// Read the rooms from a lobby saved with --dump instead of fetching them
+    #[arg(long, value_name = "PATH")]
+    pub from_file: Option<PathBuf>,
+
+    /// Compare two lobbies saved with --dump, and print the rooms that were added,
+    /// removed or changed in players between them. Only --format json and --schema
+    /// apply. The saved lobbies have no pings to compare
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"])]
+    pub diff: Option<Vec<PathBuf>>,
+
+    /// Ping the addresses read from stdin instead of the lobby's rooms, one per line
+    /// with an optional :port
+    #[arg(
+        long,
+        conflicts_with_all = ["from_file", "list_games", "monitor", "favorites", "interactive"]
+    )]
+    pub stdin: bool,
+
+    /// Save the lobby to this file before filtering it, to be read back with --from-file.
+    /// It's always saved in the yuzu schema
+    #[arg(long, value_name = "PATH")]
+    pub dump: Option<PathBuf>,
+
+    /// How many rooms to ping at the same time. "auto" starts with a few and pings more
+    /// at a time while the pings keep up, up to 64
+    #[arg(long, env = "YUZU_CONCURRENCY", default_value = "10")]
+    pub concurrency: Concurrency,
+
+    /// How many threads to run on, by default one per CPU or just one with
+    /// --concurrency 1
+    #[arg(
+        long,
+        env = "TOKIO_WORKER_THREADS",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub threads: Option<usize>,
+
+    /// Only list the rooms, without pinging them. They're sorted by players instead of
+    /// ping
+    #[arg(long, conflicts_with_all = ["max_ping", "stop_after", "launch"])]
+    pub no_ping: bool,
+
+    /// How many probes to send to each room
+    #[arg(
+        long,
+        env = "YUZU_PING_COUNT",
+        default_value_t = 3,
+        value_parser = value_parser!(u8).range(1..),
+    )]
+    pub count: u8,
+
+    /// Ping rooms that didn't reply at all up to this many more times before calling
+    /// them unreachable
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub ping_retries: u32,
+
+    /// Send each room one more probe ahead of the others and leave its reply out, since
+    /// the first one tends to be slow while routes and caches are set up. Makes pinging
+    /// take as long as one more probe
+    #[arg(long, conflicts_with = "monitor")]
+    pub warmup: bool,
+
+    /// Milliseconds to wait for each reply
+    #[arg(long, env = "YUZU_PING_TIMEOUT", default_value_t = 500)]
+    pub timeout: u64,
+
+    /// Ping the rooms that didn't reply within --timeout once more, waiting up to this
+    /// many milliseconds for each reply, so that far away rooms aren't taken as
+    /// unreachable without waiting that long on every room
+    #[arg(long, value_name = "MS", conflicts_with = "monitor")]
+    pub timeout_escalation: Option<u64>,
+
+    /// How to measure the latency to each room
+    #[arg(long, value_enum, default_value_t = ProbeArg::Icmp)]
+    pub probe: ProbeArg,
+
+    /// Ping the rooms that don't reply to --probe again with this, rather than take
+    /// them as unreachable straight away. Unlike --probe both, it's only sent when
+    /// needed. The summary tells how many rooms only replied to it
+    #[arg(long, value_enum, default_value_t = FallbackArg::None)]
+    pub fallback_probe: FallbackArg,
+
+    /// Ping with this command instead of the system ping, with {address}, {count} and
+    /// {timeout} in milliseconds filled in, like "fping -c {count} {address}". Only for
+    /// --probe icmp and both
+    #[arg(long, env = "YUZU_PING_CMD", value_name = "COMMAND", value_parser = PingCommand::parse)]
+    pub ping_cmd: Option<PingCommand>,
+
+    /// Seconds to wait for the lobby to respond
+    #[arg(long, env = "YUZU_HTTP_TIMEOUT", default_value_t = 10)]
+    pub http_timeout: u64,
+
+    /// User-Agent to fetch the lobby with
+    #[arg(long, default_value = concat!("yuzuping/", env!("CARGO_PKG_VERSION")))]
+    pub user_agent: String,
+
+    /// Proxy to fetch the lobby through. Takes precedence over the HTTP_PROXY, HTTPS_PROXY
+    /// and ALL_PROXY environment variables, which are used otherwise
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Token to fetch the lobby with, for mirrors that want one. Sent as a bearer token,
+    /// and never to the webhook
+    #[arg(
+        long,
+        env = "YUZU_LOBBY_TOKEN",
+        hide_env_values = true,
+        value_name = "TOKEN"
+    )]
+    pub auth_bearer: Option<String>,
+
+    /// User and password to fetch the lobby with, for mirrors that want them. Takes
+    /// precedence over --auth-bearer
+    #[arg(long, value_name = "USER:PASSWORD")]
+    pub auth_basic: Option<String>,
+
+    /// How many times to retry fetching the lobby when it can't be reached
+    #[arg(long, env = "YUZU_FETCH_RETRIES", default_value_t = 2)]
+    pub retries: u32,
+
+    /// Hide rooms with fewer players than this
+    #[arg(long, default_value_t = 0)]
+    pub min_players: usize,
+
+    /// Hide rooms that can't take another player
+    #[arg(long)]
+    pub hide_full: bool,
+
+    /// Only show rooms with at least 2 players and a free slot, the same as
+    /// --min-players 2 --hide-full
+    #[arg(long)]
+    pub active: bool,
+
+    /// Hide rooms that need a password to join
+    #[arg(long)]
+    pub no_password: bool,
+
+    /// Hide rooms where some players are running a different game than the room is
+    /// hosting, usually another version that can't join. Otherwise they're marked
+    /// [mismatch]
+    #[arg(long)]
+    pub strict_version: bool,
+
+    /// Only show rooms with a player whose nickname contains this, ignoring case
+    #[arg(long, value_name = "NAME")]
+    pub find_player: Option<String>,
+
+    /// Only show the rooms named exactly this, to keep an eye on one in particular
+    #[arg(long, value_name = "NAME", conflicts_with = "room_contains")]
+    pub room: Option<String>,
+
+    /// Only show rooms whose name contains this, ignoring case
+    #[arg(long, value_name = "TEXT")]
+    pub room_contains: Option<String>,
+
+    /// Only show rooms whose description contains this, ignoring case, like "no items".
+    /// Rooms without a description are left out
+    #[arg(long, value_name = "TEXT")]
+    pub description_contains: Option<String>,
+
+    /// Skip the rooms at the addresses and networks in this file, one per line like
+    /// `203.0.113.7`, `198.51.100.0/24` or `host.example`, without pinging them
+    #[arg(long, value_name = "FILE")]
+    pub exclude: Option<PathBuf>,
+
+    /// Only ping and show the rooms at the addresses and networks in this file, in the
+    /// same format as --exclude
+    #[arg(long, value_name = "FILE")]
+    pub include_only: Option<PathBuf>,
+
+    /// Label the rooms at some addresses with names of your own, from a JSON object
+    /// like {"203.0.113.7": "Alice's PC", "198.51.100.2:24873": "the club"}
+    #[arg(long, value_name = "FILE")]
+    pub labels: Option<PathBuf>,
+
+    /// Replace the room names and player nicknames with placeholders like "room-9c1d2e"
+    /// everywhere they'd be shown, for sharing the output. The same name gets the same
+    /// placeholder throughout a run, but a different one the next. Implies not pointing
+    /// out what changed since the last run, nor remembering this one's rooms
+    #[arg(long)]
+    pub anonymize: bool,
+
+    /// Only show rooms hosted at one of the addresses saved with --add-favorite, and
+    /// point out the ones that aren't in the lobby
+    #[arg(long)]
+    pub favorites: bool,
+
+    /// Save this address as a favorite, then exit. Can be repeated or comma separated
+    #[arg(long, value_name = "ADDRESS", value_delimiter = ',')]
+    pub add_favorite: Vec<String>,
+
+    /// Forget this favorite address, then exit. Can be repeated or comma separated
+    #[arg(long, value_name = "ADDRESS", value_delimiter = ',')]
+    pub remove_favorite: Vec<String>,
+
+    /// Exit successfully even when no rooms match or none are reachable, rather than
+    /// with 4 or 5
+    #[arg(long)]
+    pub ignore_empty: bool,
+
+    /// Fail as soon as something's wrong with the network: exit without retrying when a
+    /// lobby can't be fetched, and with 5 unless --require-reachable rooms, or at least
+    /// one, replied. Also stops --watch at the first failure
+    #[arg(long, conflicts_with = "ignore_empty")]
+    pub fail_fast: bool,
+
+    /// Exit with 5 unless at least this many rooms replied, after printing them
+    #[arg(long, value_name = "N", conflicts_with_all = ["no_ping", "ignore_empty"])]
+    pub require_reachable: Option<usize>,
+
+    /// Don't merge rooms with the same name and mostly the same players, which are
+    /// usually one host listed at several of its addresses
+    #[arg(long)]
+    pub no_merge: bool,
+
+    /// Tell on stderr how many rooms were listed, hosting the game, left after the other
+    /// filters and reachable, to see where they all went
+    #[arg(long)]
+    pub count_summary: bool,
+
+    /// Show the latency of each reply as a sparkline next to the ping, like ▁▂▁█▂, to
+    /// tell steady rooms from jittery ones. Also shown with --verbose
+    #[arg(long)]
+    pub sparkline: bool,
+
+    /// Show how full each room is as a bar next to its players, like 3/8 [███░░░░░]
+    #[arg(long)]
+    pub bars: bool,
+
+    /// Tell on stderr how long fetching the lobby, resolving the addresses and pinging
+    /// took, to see where the time went
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Print every game hosted in the lobby and how many rooms host it, then exit
+    #[arg(long)]
+    pub list_games: bool,
+
+    /// Drop rooms whose minimum ping is above this many milliseconds, and every
+    /// unreachable room
+    #[arg(long, value_name = "MS")]
+    pub max_ping: Option<u64>,
+
+    /// Stop pinging once this many rooms have replied, for when any few good rooms will
+    /// do. The rooms left unpinged are treated as unreachable
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub stop_after: Option<usize>,
+
+    /// Only ping this many rooms picked at random out of those that passed the filters,
+    /// for a rough idea of a large lobby. The rest are treated as unreachable
+    #[arg(long, value_name = "N", conflicts_with = "no_ping", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub sample: Option<usize>,
+
+    /// Pick the same rooms for --sample every time, out of the same lobby
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Only count rooms with a minimum ping below this many milliseconds towards
+    /// --stop-after
+    #[arg(long, value_name = "MS", requires = "stop_after")]
+    pub stop_below: Option<u64>,
+
+    /// What to sort the rooms by
+    #[arg(long, visible_alias = "rank", value_enum, default_value_t = SortKey::Ping)]
+    pub sort: SortKey,
+
+    /// What to sort the rooms that --sort can't tell apart by, before going by their
+    /// name
+    #[arg(long, value_enum, default_value_t = SortKey::Players)]
+    pub sort_secondary: SortKey,
+
+    /// How many milliseconds of ping each player makes up for with --sort score
+    #[arg(long, value_name = "MS", default_value_t = 10.0)]
+    pub player_weight: f64,
+
+    /// Which part of the ping statistics to sort by
+    #[arg(long, value_enum, default_value_t = Stat::Avg)]
+    pub sort_stat: Stat,
+
+    /// Sort in the opposite order. The rooms that didn't reply still go at the end, or
+    /// the top with --unreachable-first, and those nobody's playing in below the rest
+    /// with --bots-last
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// Keep the rooms of each --game together under a heading, in the order the games
+    /// were given, instead of sorting them all into one list
+    #[arg(long)]
+    pub group_by_game: bool,
+
+    /// Keep the rooms that didn't reply at the end of the list, whatever the sort. This
+    /// is where they go by default, but the table and plain output only list them when
+    /// asked for
+    #[arg(long, conflicts_with_all = ["unreachable_first", "unreachable_hide"])]
+    pub unreachable_last: bool,
+
+    /// Put the rooms nobody's playing in below the ones with players, whatever their
+    /// ping, since they tend to be bots or placeholders
+    #[arg(long)]
+    pub bots_last: bool,
+
+    /// Put the rooms that didn't reply at the top of the list, the most populated first
+    #[arg(long, conflicts_with = "unreachable_hide")]
+    pub unreachable_first: bool,
+
+    /// Leave out the rooms that didn't reply
+    #[arg(long, conflicts_with = "no_ping")]
+    pub unreachable_hide: bool,
+
+    /// List the rooms that didn't reply in the table and plain output too, marked as
+    /// unreachable, where --unreachable-first or the default puts them
+    #[arg(long, conflicts_with_all = ["no_ping", "unreachable_hide"])]
+    pub show_unreachable: bool,
+
+    /// Only print this many rooms from the top of the sorted list
+    #[arg(long, visible_alias = "top")]
+    pub limit: Option<usize>,
+
+    /// Look up the reverse DNS name of each room and show it next to its address
+    #[arg(long)]
+    pub resolve: bool,
+
+    /// Ping every address a room's hostname resolves to, rather than only the first, and
+    /// keep the quickest
+    #[arg(long)]
+    pub all_addrs: bool,
+
+    /// Label each room with its country from this MaxMind GeoIP2 or GeoLite2 database
+    #[cfg(feature = "geoip")]
+    #[arg(long, env = "YUZU_GEOIP_DB", value_name = "PATH")]
+    pub geoip_db: Option<PathBuf>,
+
+    /// Only show rooms in this country, by its two letter code like SE
+    #[cfg(feature = "geoip")]
+    #[arg(
+        long,
+        value_name = "CC",
+        requires = "geoip_db",
+        conflicts_with = "no_ping"
+    )]
+    pub region: Option<String>,
+
+    /// Cut room names longer than this many columns short with an ellipsis. The table
+    /// does so at 32 columns by default, and JSON always has the full name
+    #[arg(long, value_name = "COLUMNS", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub name_width: Option<usize>,
+
+    /// List the players of each room under it
+    #[arg(long)]
+    pub show_players: bool,
+
+    /// Print the description of each room under it
+    #[arg(long)]
+    pub show_description: bool,
+
+    /// Show the min/avg/max ping, jitter and 50th/95th percentiles instead of only the
+    /// minimum
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Color pings below this many milliseconds green
+    #[arg(
+        long,
+        visible_alias = "ping-good",
+        value_name = "MS",
+        default_value_t = 60
+    )]
+    pub green_below: u64,
+
+    /// Color pings below this many milliseconds yellow, and the rest red. Has to be above
+    /// --green-below
+    #[arg(
+        long,
+        visible_alias = "ping-ok",
+        value_name = "MS",
+        default_value_t = 120
+    )]
+    pub yellow_below: u64,
+
+    /// Only log warnings and errors, RUST_LOG can be used for finer control
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Print each room as soon as it has been pinged, before the sorted list
+    #[arg(long)]
+    pub live: bool,
+
+    /// Don't show which rooms have been pinged so far
+    #[arg(long)]
+    pub no_progress: bool,
+
+    /// Wait for enter before exiting, which by default only happens when the executable
+    /// was double-clicked on windows and its window would otherwise close
+    #[arg(long, overrides_with = "no_pause")]
+    pub pause: bool,
+
+    /// Never wait for enter before exiting
+    #[arg(long, overrides_with = "pause")]
+    pub no_pause: bool,
+
+    /// Keep refreshing the list every this many seconds until interrupted
+    #[arg(long, value_name = "SECONDS")]
+    pub watch: Option<u64>,
+
+    /// Like --watch, for screens left unattended: keep refreshing every this many
+    /// seconds until interrupted, taking an empty lobby as nothing out of the ordinary
+    /// and trying again after --loop-backoff when the lobby can't be fetched
+    #[arg(long = "loop", value_name = "SECONDS", conflicts_with = "fail_fast")]
+    pub loop_every: Option<u64>,
+
+    /// How many seconds to wait before trying again when a refresh of --loop failed
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 30,
+        requires = "loop_every"
+    )]
+    pub loop_backoff: u64,
+
+    /// How many refreshes of each room's ping to show the trend of while watching,
+    /// along with whether it went up or down since the refresh before
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = RangedU64ValueParser::<usize>::new().range(2..))]
+    pub history: usize,
+
+    /// Keep pinging this one address, with an optional :port for --probe tcp or udp,
+    /// and show how the last replies went until interrupted. The lobby isn't fetched
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        conflicts_with_all = ["refreshing", "interactive", "launch", "list_games", "best_only", "print_address"]
+    )]
+    pub monitor: Option<String>,
+
+    /// Post the rooms that show up while watching to this discord webhook. The rooms of
+    /// the first refresh count as already known
+    #[arg(
+        long,
+        env = "YUZU_WEBHOOK",
+        value_name = "URL",
+        requires = "refreshing"
+    )]
+    pub webhook: Option<String>,
+
+    /// Stream each refresh to the programs connected to a unix socket at this path, or a
+    /// named pipe like \\.\pipe\yuzuping on windows, one JSON message per line: a
+    /// {"type": "refresh"} as the pinging starts, a {"type": "room", "room": {..}} with
+    /// the keys of --format ndjson as each room is pinged, and a {"type": "done",
+    /// "rooms": N, "reachable": N} at the end
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["no_ping", "monitor"])]
+    pub ipc: Option<PathBuf>,
+
+    /// Serve the room counts and best pings of each refresh at http://ADDR/metrics while
+    /// watching, for prometheus to scrape
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDR", requires = "refreshing")]
+    pub metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Pick a room with the arrow keys instead of printing them, then copy its address or
+    /// join it with the --launch-cmd
+    #[arg(long, conflicts_with_all = ["refreshing", "launch", "format"])]
+    pub interactive: bool,
+
+    /// Join the room numbered this in the # column of the table, by running the
+    /// --launch-cmd
+    #[arg(long, value_name = "INDEX", conflicts_with = "refreshing")]
+    pub launch: Option<usize>,
+
+    /// Command to join a room with, where {address} and {port} are replaced by the
+    /// room's. Split on whitespace, without going through a shell
+    #[arg(long, env = "YUZU_LAUNCH_CMD", value_name = "COMMAND")]
+    pub launch_cmd: Option<String>,
+
+    /// Always fetch the lobby, and don't remember this run's rooms nor point out what
+    /// changed since the last one
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Use the lobby fetched by an earlier run, or refresh of --watch, for this many
+    /// seconds rather than fetching it again. 0 always fetches it
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    pub cache_ttl: u64,
+
+    /// Print each room like this instead, with {name}, {game}, {address}, {host}, {port},
+    /// {players}, {ping}, {loss} and {locked} filled in. Implies --format plain
+    #[arg(long, value_name = "TEMPLATE", value_parser = Template::<Field>::parse)]
+    pub format_template: Option<Template>,
+
+    /// How to print the rooms
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+
+    /// Also write the rooms and their summary to this file as JSON, whatever the
+    /// --format. It's replaced all at once rather than written bit by bit
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Indent the --format json output, and the --report, for reading
+    #[arg(long)]
+    pub json_pretty: bool,
+
+    /// Print --format json as a bare array of the rooms, the way it used to be, rather
+    /// than as an object with how many rooms there were before filtering
+    #[arg(long)]
+    pub flat_json: bool,
+
+    /// Only include these keys in the --format json and ndjson output, in this order
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(JSON_FIELDS)
+    )]
+    pub fields: Vec<String>,
+
+    /// Only print the ping and name of the closest room, for status bars and scripts.
+    /// Exits with 5 when no room replied
+    #[arg(
+        long,
+        conflicts_with_all = ["no_ping", "interactive", "launch", "format", "format_template"]
+    )]
+    pub best_only: bool,
+
+    /// Only print the address:port of the closest room, to join it from a script.
+    /// Exits with 5 when no room replied
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "no_ping", "interactive", "launch", "format", "format_template", "best_only"
+        ]
+    )]
+    pub print_address: bool,
+
+    /// Only print a line summing up the rooms, for status bars, like "Super Smash Bros.
+    /// Ultimate: 28 rooms, best 12.3 ms". Can be given a template of its own instead,
+    /// with {game}, {rooms}, {reachable}, {players}, {best} and {best_name} filled in
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        num_args = 0..=1,
+        default_missing_value = Template::<StatusField>::DEFAULT,
+        value_parser = Template::<StatusField>::parse,
+        conflicts_with_all = [
+            "interactive", "launch", "format", "format_template", "best_only", "print_address"
+        ]
+    )]
+    pub statusline: Option<Template<StatusField>>,
+
+    /// Check on the lobby like a nagios plugin: print a line like "OK: 3 rooms under
+    /// 80ms", and exit with 0 when a room is reachable, under --max-ping if given, 1
+    /// when none is, 2 when the lobby can't be fetched and 3 when something else failed
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "interactive", "launch", "format_template", "best_only", "print_address",
+            "statusline", "monitor", "refreshing", "ignore_empty", "no_ping"
+        ]
+    )]
+    pub check: bool,
+}
+
+impl Args {
+    /// Whether the output is meant for a person, see [`Format::is_interactive`].
+    pub fn is_interactive(&self) -> bool {
+        self.format.is_interactive()
+            && !self.best_only
+            && !self.print_address
+            && self.statusline.is_none()
+            && !self.check
+    }
+
+    /// How often --watch or --loop refresh, if either is given.
+    pub fn refresh_every(&self) -> Option<Duration> {
+        self.watch.or(self.loop_every).map(Duration::from_secs)
+    }
+
+    /// The games asked for with --game, leaving out the blank ones that an exported but
+    /// empty YUZU_GAME_NAME or a stray comma make.
+    pub fn games(&self) -> Vec<String> {
+        self.game
+            .iter()
+            .map(|game| game.trim())
+            .filter(|game| !game.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Whether --game was only given blank, which shows every game rather than none.
+    pub fn every_game(&self) -> bool {
+        !self.game.is_empty() && self.games().is_empty()
+    }
+
+    /// Whether each game's rooms are listed under a heading of their own, which they
+    /// are when every game is shown.
+    pub fn group_by_game(&self) -> bool {
+        self.group_by_game || self.every_game()
+    }
+
+    /// How many rooms have to reply for the run to succeed, if it matters.
+    pub fn required_reachable(&self) -> Option<usize> {
+        match self.require_reachable {
+            None if self.fail_fast && !self.no_ping => Some(1),
+            required => required,
+        }
+    }
+
+    /// How to ping the rooms. The games are left out, since they're matched loosely
+    /// and against the whole lobby rather than by the library.
+    pub fn ping_config(&self) -> PingConfig {
+        let config = PingConfig::builder()
+            .concurrency(self.concurrency.max())
+            .adaptive(self.concurrency == Concurrency::Auto)
+            .probe(self.probe.into())
+            .count(self.count)
+            .timeout(Duration::from_millis(self.timeout))
+            .retries(self.ping_retries)
+            .warmup(self.warmup)
+            .reverse_dns(self.resolve)
+            .all_addresses(self.all_addrs);
+        let config = match self.fallback_probe.probe() {
+            Some(fallback) => config.fallback(fallback),
+            None => config,
+        };
+        match &self.ping_cmd {
+            Some(command) => config.command(command.clone()).build(),
+            None => config.build(),
+        }
+    }
+}
+
+/// How many rooms to ping at the same time, see --concurrency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concurrency {
+    Auto,
+    Fixed(usize),
+}
+
+impl Concurrency {
+    /// How many pings --concurrency auto ramps up to.
+    const AUTO_MAX: usize = 64;
+
+    /// The most rooms that are pinged at once.
+    pub fn max(self) -> usize {
+        match self {
+            Concurrency::Auto => Self::AUTO_MAX,
+            Concurrency::Fixed(concurrency) => concurrency,
+        }
+    }
+
+    /// The fewest rooms that are pinged at once, which for auto is the few it starts
+    /// out with and never backs off below.
+    pub fn least(self) -> usize {
+        match self {
+            Concurrency::Auto => 4,
+            Concurrency::Fixed(concurrency) => concurrency,
+        }
+    }
+}
+
+impl std::str::FromStr for Concurrency {
+    type Err = String;
+
+    fn from_str(concurrency: &str) -> Result<Self, String> {
+        if concurrency.eq_ignore_ascii_case("auto") {
+            return Ok(Concurrency::Auto);
+        }
+        match concurrency.parse::<usize>() {
+            Ok(0) => Err("at least one room has to be pinged at a time".to_string()),
+            Ok(concurrency) => Ok(Concurrency::Fixed(concurrency)),
+            Err(_) => Err("expected a number of rooms, or auto".to_string()),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeArg {
+    /// ICMP echo through the system ping command
+    Icmp,
+    /// Time a TCP connection to the room's port, for hosts that drop ICMP
+    Tcp,
+    /// Time the answer to a datagram sent to the room's port. Best-effort, since rooms
+    /// that don't answer at all count as unreachable
+    Udp,
+    /// Send ICMP echo directly, which may need administrator or setcap
+    #[cfg(feature = "icmp")]
+    Native,
+    /// Both icmp and tcp at once, taking the icmp ping when both get one. --verbose
+    /// tells which each room's is from
+    Both,
+}
+
+impl From<ProbeArg> for Probe {
+    fn from(probe: ProbeArg) -> Self {
+        match probe {
+            ProbeArg::Icmp => Probe::Icmp,
+            ProbeArg::Tcp => Probe::Tcp,
+            ProbeArg::Udp => Probe::Udp,
+            #[cfg(feature = "icmp")]
+            ProbeArg::Native => Probe::Native,
+            ProbeArg::Both => Probe::Both,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackArg {
+    /// Connecting over TCP to the room's port
+    Tcp,
+    /// A datagram to the room's port
+    Udp,
+    /// Take the rooms that don't reply as unreachable
+    None,
+}
+
+impl FallbackArg {
+    pub fn probe(self) -> Option<Probe> {
+        match self {
+            FallbackArg::Tcp => Some(Probe::Tcp),
+            FallbackArg::Udp => Some(Probe::Udp),
+            FallbackArg::None => None,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaArg {
+    /// The yuzu lobby
+    Yuzu,
+    /// The suyu lobby, much the same as yuzu's
+    Suyu,
+    /// The ryujinx LDN lobby
+    Ryujinx,
+}
+
+impl From<SchemaArg> for LobbySchema {
+    fn from(schema: SchemaArg) -> Self {
+        match schema {
+            SchemaArg::Yuzu => LobbySchema::Yuzu,
+            SchemaArg::Suyu => LobbySchema::Suyu,
+            SchemaArg::Ryujinx => LobbySchema::Ryujinx,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Closest first
+    Ping,
+    /// Most players first
+    Players,
+    /// Alphabetically by room name
+    Name,
+    /// Best score first, which is the ping in milliseconds less --player-weight for
+    /// every player. For the best room to recommend, when an empty room nearby isn't it
+    Score,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    Min,
+    Avg,
+    Max,
+    P50,
+    P95,
+}
+
+impl Stat {
+    pub fn of(self, stats: &PingStats) -> Duration {
+        match self {
+            Stat::Min => stats.min,
+            Stat::Avg => stats.avg,
+            Stat::Max => stats.max,
+            Stat::P50 => stats.p50,
+            Stat::P95 => stats.p95,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A table with a column for each detail, closest last
+    Table,
+    /// One line per room, closest last
+    Plain,
+    /// A JSON object of how many rooms there were before filtering and how many are
+    /// shown under "meta", with the rooms closest first under "rooms". Or an object
+    /// with the error and exit code when there are none to show
+    Json,
+    /// A CSV table of the rooms with a header, closest first
+    Csv,
+    /// A line of JSON for each room as soon as it's pinged, in no particular order, and
+    /// one with the error and exit code if the run fails
+    Ndjson,
+}
+
+impl Format {
+    /// Whether the output is meant for a person rather than another program, in which
+    /// case progress and the exit prompt are shown.
+    pub fn is_interactive(self) -> bool {
+        matches!(self, Format::Table | Format::Plain)
+    }
+}
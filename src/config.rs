@@ -0,0 +1,169 @@
+use crate::{Error, FallbackPinger, PingCommand, Probe, ProbePinger};
+use std::time::Duration;
+
+/// How to fetch and ping the rooms, for [`fetch_rooms`](crate::fetch_rooms) and
+/// [`ping_rooms`](crate::ping_rooms). Made with [`PingConfig::builder`], which starts
+/// from the same defaults as the command line.
+#[derive(Debug, Clone)]
+pub struct PingConfig {
+    /// How many rooms to ping at the same time, or at most with `adaptive`.
+    pub concurrency: usize,
+    /// Start out pinging a few rooms at a time, and ping more at a time while the pings
+    /// keep taking as long and timing out as often as they did.
+    pub adaptive: bool,
+    /// How to measure the latency to each room.
+    pub probe: Probe,
+    /// How many probes to send each room.
+    pub count: u8,
+    /// How long to wait for each reply.
+    pub timeout: Duration,
+    /// How many more times to ping a room that didn't reply at all.
+    pub retries: u32,
+    /// Send one more probe ahead of the `count` that are measured, whose reply is left
+    /// out, since the first tends to be slow while routes and caches are set up.
+    pub warmup: bool,
+    /// Whether to look up the `hostname` of each room while pinging it.
+    pub reverse_dns: bool,
+    /// Ping every address a room's hostname resolves to rather than only the first,
+    /// keeping the quickest as the room's `ip`.
+    pub all_addresses: bool,
+    /// Only fetch the rooms hosting this game, or every room when `None`.
+    pub game: Option<String>,
+    /// Ping with this instead of the system `ping`, for [`Probe::Icmp`] and
+    /// [`Probe::Both`].
+    pub command: Option<PingCommand>,
+    /// Ping the rooms that don't reply to the `probe` again with this one.
+    pub fallback: Option<Probe>,
+}
+
+impl PingConfig {
+    pub fn builder() -> PingConfigBuilder {
+        PingConfigBuilder {
+            config: PingConfig::default(),
+        }
+    }
+
+    /// Set up the pinger for the `probe`, `count`, `timeout`, `command` and `fallback`,
+    /// sending one more probe with `warmup`.
+    pub fn pinger(&self) -> Result<FallbackPinger<ProbePinger, ProbePinger>, Error> {
+        let fallback = match self.fallback {
+            Some(fallback) => Some((fallback, self.probe_pinger(fallback)?)),
+            None => None,
+        };
+        Ok(FallbackPinger {
+            primary: (self.probe, self.probe_pinger(self.probe)?),
+            fallback,
+        })
+    }
+
+    fn probe_pinger(&self, probe: Probe) -> Result<ProbePinger, Error> {
+        let count = self.count.saturating_add(self.warmup.into());
+        let pinger = ProbePinger::new(probe, count, self.timeout)?;
+        let Some(command) = &self.command else {
+            return Ok(pinger);
+        };
+
+        Ok(match pinger {
+            ProbePinger::Icmp(pinger) => ProbePinger::Icmp(pinger.with_command(command.clone())),
+            ProbePinger::Both(mut pinger) => {
+                let (probe, preferred) = pinger.preferred;
+                pinger.preferred = (probe, preferred.with_command(command.clone()));
+                ProbePinger::Both(pinger)
+            }
+            pinger => pinger,
+        })
+    }
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        PingConfig {
+            concurrency: 10,
+            adaptive: false,
+            probe: Probe::Icmp,
+            count: 3,
+            timeout: Duration::from_millis(500),
+            retries: 0,
+            warmup: false,
+            reverse_dns: false,
+            all_addresses: false,
+            game: None,
+            command: None,
+            fallback: None,
+        }
+    }
+}
+
+/// Builds a [`PingConfig`], see its fields for what each setting does.
+#[derive(Debug, Clone)]
+pub struct PingConfigBuilder {
+    config: PingConfig,
+}
+
+impl PingConfigBuilder {
+    /// At least one, a concurrency of zero is taken as one.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.config.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn adaptive(mut self, adaptive: bool) -> Self {
+        self.config.adaptive = adaptive;
+        self
+    }
+
+    pub fn probe(mut self, probe: Probe) -> Self {
+        self.config.probe = probe;
+        self
+    }
+
+    /// At least one, a count of zero is taken as one.
+    pub fn count(mut self, count: u8) -> Self {
+        self.config.count = count.max(1);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.config.retries = retries;
+        self
+    }
+
+    pub fn warmup(mut self, warmup: bool) -> Self {
+        self.config.warmup = warmup;
+        self
+    }
+
+    pub fn reverse_dns(mut self, reverse_dns: bool) -> Self {
+        self.config.reverse_dns = reverse_dns;
+        self
+    }
+
+    pub fn all_addresses(mut self, all_addresses: bool) -> Self {
+        self.config.all_addresses = all_addresses;
+        self
+    }
+
+    pub fn game(mut self, game: impl Into<String>) -> Self {
+        self.config.game = Some(game.into());
+        self
+    }
+
+    pub fn fallback(mut self, fallback: Probe) -> Self {
+        self.config.fallback = Some(fallback);
+        self
+    }
+
+    pub fn command(mut self, command: PingCommand) -> Self {
+        self.config.command = Some(command);
+        self
+    }
+
+    pub fn build(self) -> PingConfig {
+        self.config
+    }
+}
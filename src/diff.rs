@@ -0,0 +1,106 @@
+use crate::output::clean;
+use serde::Serialize;
+use std::collections::HashMap;
+use yuzuping::{Response, Room};
+
+/// How one lobby differs from an earlier one, going by the rooms' addresses and ports
+/// like the cache of the previous run does.
+#[derive(Serialize)]
+pub struct Diff<'a> {
+    /// The rooms that are only in the newer lobby, in its order.
+    pub added: Vec<DiffRoom<'a>>,
+    /// The rooms that are only in the older lobby, in its order.
+    pub removed: Vec<DiffRoom<'a>>,
+    /// The rooms in both whose number of players changed, in the newer lobby's order.
+    pub changed: Vec<DiffRoom<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct DiffRoom<'a> {
+    name: &'a str,
+    game: &'a str,
+    address: &'a str,
+    port: u32,
+    players: usize,
+    /// Left out for rooms that were added or removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    players_before: Option<usize>,
+}
+
+impl<'a> DiffRoom<'a> {
+    fn new(room: &'a Room, before: Option<&Room>) -> Self {
+        DiffRoom {
+            name: &room.name,
+            game: &room.game_name,
+            address: &room.address,
+            port: room.port,
+            players: room.players.len(),
+            players_before: before.map(|before| before.players.len()),
+        }
+    }
+}
+
+impl<'a> Diff<'a> {
+    pub fn new(old: &'a Response, new: &'a Response) -> Self {
+        let key = |room: &'a Room| (room.address.as_str(), room.port);
+        let old_rooms = old.rooms.iter().map(|room| (key(room), room));
+        let old_rooms = old_rooms.collect::<HashMap<_, _>>();
+        let new_rooms = new.rooms.iter().map(|room| (key(room), room));
+        let new_rooms = new_rooms.collect::<HashMap<_, _>>();
+
+        let mut diff = Diff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        for room in &new.rooms {
+            match old_rooms.get(&key(room)) {
+                None => diff.added.push(DiffRoom::new(room, None)),
+                Some(before) if before.players.len() != room.players.len() => {
+                    diff.changed.push(DiffRoom::new(room, Some(before)));
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .rooms
+            .iter()
+            .filter(|room| !new_rooms.contains_key(&key(room)))
+            .map(|room| DiffRoom::new(room, None))
+            .collect();
+
+        diff
+    }
+
+    /// Print a line for each room, `+` for the added ones, `-` for the removed and `~`
+    /// for the changed, then how many of each there were.
+    pub fn print(&self) {
+        let line = |sign: &str, room: &DiffRoom| {
+            let players = match room.players_before {
+                Some(before) => format!("{before} → {} playing", room.players),
+                None => format!("{} playing", room.players),
+            };
+            println!(
+                "{sign} {} [{}] ({players})",
+                clean(room.name),
+                clean(room.game)
+            );
+        };
+
+        for room in &self.added {
+            line("+", room);
+        }
+        for room in &self.removed {
+            line("-", room);
+        }
+        for room in &self.changed {
+            line("~", room);
+        }
+        println!(
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        );
+    }
+}
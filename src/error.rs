@@ -0,0 +1,30 @@
+/// Everything that can go wrong while fetching and pinging rooms.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The lobby couldn't be reached, or didn't respond successfully.
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    /// The lobby responded with something other than a list of rooms.
+    #[error("the lobby isn't a list of rooms: {0}")]
+    Decode(#[from] serde_json::Error),
+    /// A room couldn't be probed at all, which is different from it not replying.
+    #[error("unable to ping: {0}")]
+    Ping(std::io::Error),
+    /// The program to ping with isn't installed, so no room can be pinged with it.
+    #[error("{0} wasn't found")]
+    PingNotFound(String),
+    /// `ping` printed neither replies nor a summary we recognize, usually an error of its
+    /// own.
+    #[error("couldn't make sense of ping's output: {0}")]
+    Parse(String),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// Whether the lobby was JSON, just not shaped like any lobby we know, as happens when
+    /// its API changes rather than when something other than the lobby answered.
+    pub fn is_unfamiliar_schema(&self) -> bool {
+        matches!(self, Error::Decode(err) if err.is_data())
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The hosts to look out for, by the address the lobby lists them at.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Favorites {
+    addresses: Vec<String>,
+}
+
+impl Favorites {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("yuzuping").join("favorites.json"))
+    }
+
+    /// The saved favorites, none if nothing was saved yet. Unlike the cache, a file that
+    /// can't be read is an error so that it isn't overwritten and the favorites lost.
+    pub fn load() -> std::io::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Favorites::default());
+        };
+
+        match std::fs::read(path) {
+            Ok(json) => Ok(serde_json::from_slice(&json)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Favorites::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no config directory")
+        })?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.addresses
+            .iter()
+            .any(|favorite| favorite.eq_ignore_ascii_case(address.trim()))
+    }
+
+    /// Add `address`, `false` if it already was a favorite.
+    pub fn add(&mut self, address: &str) -> bool {
+        if self.contains(address) {
+            return false;
+        }
+        self.addresses.push(address.trim().to_string());
+        true
+    }
+
+    /// Remove `address`, `false` if it wasn't a favorite.
+    pub fn remove(&mut self, address: &str) -> bool {
+        let before = self.addresses.len();
+        self.addresses
+            .retain(|favorite| !favorite.eq_ignore_ascii_case(address.trim()));
+        self.addresses.len() != before
+    }
+
+    /// The favorites that none of `addresses` are.
+    pub fn missing<'a>(&'a self, addresses: &[&str]) -> Vec<&'a str> {
+        self.addresses
+            .iter()
+            .filter(|favorite| {
+                !addresses
+                    .iter()
+                    .any(|address| favorite.eq_ignore_ascii_case(address.trim()))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
@@ -0,0 +1,41 @@
+use crate::Room;
+use maxminddb::{path, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Looks up which country an IP is in, from a MaxMind GeoIP2 or GeoLite2 database that's
+/// read into memory once, without any network access.
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl std::fmt::Debug for GeoIp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeoIp").finish_non_exhaustive()
+    }
+}
+
+impl GeoIp {
+    /// Read the `.mmdb` database at `path`.
+    pub fn open(path: &Path) -> Result<Self, maxminddb::MaxMindDbError> {
+        Ok(GeoIp {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    /// The ISO code of the country `ip` is in, like `SE`, if the database knows.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        self.reader
+            .lookup(ip)
+            .ok()?
+            .decode_path(&path!["country", "iso_code"])
+            .ok()?
+    }
+
+    /// Fill in the `country` of every room that's been resolved.
+    pub fn locate(&self, rooms: &mut [Room]) {
+        for room in rooms {
+            room.country = room.ip.and_then(|ip| self.country(ip));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::io;
+use std::process::{Output, Stdio};
+use tokio::process::Command;
+
+/// Run `command` to the end like `Command::output` does, but in a process group of its
+/// own. Dropping the future kills the whole group rather than just the child, so that a
+/// `--ping-cmd` wrapper doesn't leave the pings it started behind on a timeout or ctrl-c.
+pub(crate) async fn output(mut command: Command) -> io::Result<Output> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let child = command.spawn()?;
+    let mut group = Group::of(&child);
+    let output = child.wait_with_output().await;
+    group.finished();
+    output
+}
+
+/// The processes started by a child, which are killed when this is dropped before the
+/// child finished.
+#[cfg(unix)]
+struct Group {
+    /// The child's id, which is also the id of its group. `None` once it finished, as
+    /// the id could be reused by then.
+    id: Option<libc::pid_t>,
+}
+
+#[cfg(unix)]
+impl Group {
+    fn of(child: &tokio::process::Child) -> Self {
+        Group {
+            id: child.id().and_then(|id| id.try_into().ok()),
+        }
+    }
+
+    fn finished(&mut self) {
+        self.id = None;
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Group {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            // a negative id signals every process in the group
+            // SAFETY: kill only takes plain integers, and the group is still ours since
+            // the child hasn't been waited on yet
+            unsafe { libc::kill(-id, libc::SIGKILL) };
+        }
+    }
+}
+
+/// The processes started by a child, as a job object that kills them once it's closed.
+/// Whatever the child starts before it's added to the job gets away, which for a ping
+/// that's only just been spawned is nothing.
+#[cfg(windows)]
+struct Group {
+    job: Option<windows_sys::Win32::Foundation::HANDLE>,
+}
+
+// SAFETY: the handle is only ever closed, which can be done from any thread
+#[cfg(windows)]
+unsafe impl Send for Group {}
+
+#[cfg(windows)]
+impl Group {
+    fn of(child: &tokio::process::Child) -> Self {
+        use windows_sys::Win32::System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+
+        let Some(process) = child.raw_handle() else {
+            return Group { job: None };
+        };
+        // SAFETY: null attributes and name make for an unnamed job with the defaults
+        let job = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if job.is_null() {
+            tracing::debug!(
+                "unable to create a job object: {}",
+                io::Error::last_os_error()
+            );
+            return Group { job: None };
+        }
+        let group = Group { job: Some(job) };
+
+        let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        // SAFETY: `limits` outlives the call and is passed along with its size, and both
+        // handles are open
+        let assigned = unsafe {
+            SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const _,
+                std::mem::size_of_val(&limits) as u32,
+            ) != 0
+                && AssignProcessToJobObject(job, process) != 0
+        };
+        if !assigned {
+            tracing::debug!(
+                "unable to set up a job object: {}",
+                io::Error::last_os_error()
+            );
+        }
+        group
+    }
+
+    /// Closing the job kills whatever the child left running, which is just as well
+    /// when it finished, so it's closed either way.
+    fn finished(&mut self) {}
+}
+
+#[cfg(windows)]
+impl Drop for Group {
+    fn drop(&mut self) {
+        if let Some(job) = self.job {
+            // SAFETY: the job is open, and closed only the once here
+            unsafe { windows_sys::Win32::Foundation::CloseHandle(job) };
+        }
+    }
+}
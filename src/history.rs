@@ -0,0 +1,91 @@
+use crate::output::sparkline;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use yuzuping::Room;
+
+// a room that's gone for longer than this is taken as a different one when it's back
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// The minimum ping of each room over the latest refreshes while watching, keyed by
+/// address and port, so that a room picks up where it left off when it's back.
+#[derive(Debug)]
+pub struct History {
+    /// How many refreshes to keep
+    len: usize,
+    rooms: Mutex<HashMap<(String, u32), RoomHistory>>,
+}
+
+#[derive(Debug)]
+struct RoomHistory {
+    /// `None` for the refreshes it didn't reply in
+    pings: VecDeque<Option<Duration>>,
+    last_seen: Instant,
+}
+
+/// How the ping of each room moved over the latest refreshes, like `▂▁▃█ ↑`.
+#[derive(Debug, Default)]
+pub struct Trends(HashMap<(String, u32), String>);
+
+impl Trends {
+    pub fn of(&self, room: &Room) -> Option<&str> {
+        self.0
+            .get(&(room.address.clone(), room.port))
+            .map(String::as_str)
+    }
+}
+
+impl History {
+    pub fn new(len: usize) -> Self {
+        History {
+            len,
+            rooms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Add the latest ping of each of `rooms`, and tell how each one has been moving.
+    pub fn record(&self, rooms: &[Room]) -> Trends {
+        let now = Instant::now();
+        let mut history = self
+            .rooms
+            .lock()
+            .expect("nothing panics while holding the lock");
+        history.retain(|_, room| now.duration_since(room.last_seen) < FORGET_AFTER);
+
+        let trends = rooms.iter().filter_map(|room| {
+            let key = (room.address.clone(), room.port);
+            let entry = history.entry(key.clone()).or_insert_with(|| RoomHistory {
+                pings: VecDeque::with_capacity(self.len + 1),
+                last_seen: now,
+            });
+            entry.last_seen = now;
+            entry
+                .pings
+                .push_back(room.ping.stats().map(|ping| ping.min));
+            while entry.pings.len() > self.len {
+                entry.pings.pop_front();
+            }
+
+            let trend = trend(&entry.pings)?;
+            Some((key, trend))
+        });
+        Trends(trends.collect())
+    }
+}
+
+/// The replied pings as a sparkline, followed by whether the latest went up or down
+/// from the one before. Nothing until there are two to compare.
+fn trend(pings: &VecDeque<Option<Duration>>) -> Option<String> {
+    let replied = pings.iter().flatten().copied().collect::<Vec<_>>();
+    let [.., before, latest] = replied[..] else {
+        return None;
+    };
+
+    // a few percent either way is just noise
+    let arrow = match latest.as_secs_f64() / before.as_secs_f64().max(f64::EPSILON) {
+        ratio if ratio > 1.05 => '↑',
+        ratio if ratio < 0.95 => '↓',
+        _ => '→',
+    };
+    Some(format!("{} {arrow}", sparkline(&replied)))
+}
@@ -0,0 +1,71 @@
+use crate::ping::{Pinger, Replies};
+use crate::Error;
+use std::net::IpAddr;
+use std::time::Duration;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
+
+/// Pings by sending ICMP echo requests directly, without spawning a process or parsing
+/// its output.
+///
+/// This needs a raw ICMP socket (or on linux a datagram one allowed by
+/// `net.ipv4.ping_group_range`), so depending on the system it has to run as
+/// administrator or be given `setcap cap_net_raw+ep`.
+pub struct IcmpPinger {
+    v4: Client,
+    v6: Option<Client>,
+    /// How many echo requests to send.
+    pub count: u8,
+    /// How long to wait for each reply.
+    pub timeout: Duration,
+}
+
+impl std::fmt::Debug for IcmpPinger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IcmpPinger")
+            .field("count", &self.count)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IcmpPinger {
+    /// Open the ICMP sockets, failing if we aren't allowed to.
+    pub fn new(count: u8, timeout: Duration) -> Result<Self, Error> {
+        Ok(IcmpPinger {
+            v4: Client::new(&Config::default()).map_err(Error::Ping)?,
+            // not every system has IPv6, so only fail once we try to use it
+            v6: Client::new(&Config::builder().kind(ICMP::V6).build()).ok(),
+            count,
+            timeout,
+        })
+    }
+}
+
+impl Pinger for IcmpPinger {
+    async fn ping(&self, ip: &IpAddr, _: u16) -> Result<Replies, Error> {
+        let client = match ip {
+            IpAddr::V4(_) => &self.v4,
+            IpAddr::V6(_) => self.v6.as_ref().ok_or_else(|| {
+                Error::Ping(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "IPv6 ICMP is unavailable",
+                ))
+            })?,
+        };
+
+        // concurrent pings share the socket, so they're told apart by their identifier
+        let mut pinger = client.pinger(*ip, PingIdentifier(rand::random())).await;
+        pinger.timeout(self.timeout);
+
+        let mut latencies = Vec::new();
+        for seq in 0..self.count {
+            match pinger.ping(PingSequence(seq as u16), &[0; 56]).await {
+                Ok((_, rtt)) => latencies.push(rtt),
+                Err(SurgeError::IOError(err)) => return Err(Error::Ping(err)),
+                Err(_) => {}
+            }
+        }
+
+        Ok(Replies::from_probes(latencies, self.count))
+    }
+}
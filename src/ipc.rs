@@ -0,0 +1,151 @@
+use crate::output::{self, JsonObject, JsonOptions};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+use yuzuping::Room;
+
+/// How many messages a slow client may fall behind by before it misses some.
+const BACKLOG: usize = 1024;
+
+/// Streams each refresh to the programs connected to a unix socket, or a named pipe on
+/// windows, as one JSON message per line:
+///
+/// - `{"type": "refresh", "fetched_at": ".."}` when a refresh starts pinging
+/// - `{"type": "room", "room": {..}}` as each room is pinged, with the same keys as
+///   --format ndjson, and only the rooms that it would print
+/// - `{"type": "done", "rooms": 12, "reachable": 9}` once every room was pinged
+///
+/// A program that connects part way through a refresh starts with the next message.
+#[derive(Debug)]
+pub struct Ipc {
+    messages: broadcast::Sender<Arc<str>>,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message<'a> {
+    Refresh { fetched_at: &'a str },
+    Room { room: &'a JsonObject },
+    Done { rooms: usize, reachable: usize },
+}
+
+impl Ipc {
+    /// Start listening at `path` in the background, replacing a socket left behind by
+    /// an earlier run.
+    pub fn listen(path: &Path) -> std::io::Result<Self> {
+        let (messages, _) = broadcast::channel(BACKLOG);
+        accept(path, messages.clone())?;
+        Ok(Ipc {
+            messages,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn refresh(&self, options: &JsonOptions) {
+        let fetched_at = output::timestamp(options.fetched_at);
+        self.send(&Message::Refresh {
+            fetched_at: &fetched_at,
+        });
+    }
+
+    pub fn room(&self, room: &Room, country: Option<&str>, options: &JsonOptions) {
+        let fetched_at = output::timestamp(options.fetched_at);
+        self.send(&Message::Room {
+            room: &options.room(room, &fetched_at, country),
+        });
+    }
+
+    pub fn done(&self, rooms: usize, reachable: usize) {
+        self.send(&Message::Done { rooms, reachable });
+    }
+
+    fn send(&self, message: &Message) {
+        let line = serde_json::to_string(message).expect("messages are always serializable");
+        // nobody listening is fine
+        let _ = self.messages.send(format!("{line}\n").into());
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Ipc {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn accept(path: &Path, messages: broadcast::Sender<Arc<str>>) -> std::io::Result<()> {
+    use tokio::net::UnixListener;
+
+    use std::os::unix::fs::FileTypeExt;
+
+    // a socket that nothing's listening on anymore only gets in the way
+    let socket = std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+    if socket && std::os::unix::net::UnixStream::connect(path).is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    let listener = UnixListener::bind(path)?;
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(forward(stream, messages.subscribe()));
+                }
+                Err(err) => tracing::debug!("unable to accept an ipc client: {err}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+fn accept(path: &Path, messages: broadcast::Sender<Arc<str>>) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    // an instance of the pipe per client, with the next one created as each connects
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(path)?;
+    let path = path.to_path_buf();
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = server.connect().await {
+                tracing::debug!("unable to accept an ipc client: {err}");
+                continue;
+            }
+            let next = match ServerOptions::new().create(&path) {
+                Ok(next) => next,
+                Err(err) => {
+                    tracing::warn!("unable to take more ipc clients: {err}");
+                    return;
+                }
+            };
+            let client = std::mem::replace(&mut server, next);
+            tokio::spawn(forward(client, messages.subscribe()));
+        }
+    });
+    Ok(())
+}
+
+/// Write every message to `client` until it goes away.
+async fn forward(mut client: impl AsyncWrite + Unpin, mut messages: broadcast::Receiver<Arc<str>>) {
+    loop {
+        let line = match messages.recv().await {
+            Ok(line) => line,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::debug!("an ipc client missed {missed} messages");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if client.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+use std::path::Path;
+use yuzuping::Room;
+
+/// Names of one's own for the rooms at some addresses, read from a JSON object of
+/// addresses to labels like `{"203.0.113.7": "Alice's PC"}`. An address can carry a
+/// `:port` to only label the room on that port.
+#[derive(Debug, Default)]
+pub struct Labels(HashMap<String, String>);
+
+impl Labels {
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let labels = serde_json::from_slice::<HashMap<String, String>>(&std::fs::read(path)?)?;
+        let labels = labels
+            .into_iter()
+            .map(|(address, label)| (address.trim().to_lowercase(), label))
+            .collect();
+        Ok(Labels(labels))
+    }
+
+    /// Label every room at an address on the list, preferring the label for its port.
+    pub fn apply(&self, rooms: &mut [Room]) {
+        for room in rooms {
+            let address = room.address.trim().to_lowercase();
+            let with_port = if address.contains(':') {
+                format!("[{address}]:{}", room.port)
+            } else {
+                format!("{address}:{}", room.port)
+            };
+            let label = self.0.get(&with_port).or_else(|| self.0.get(&address));
+            room.label = label.cloned();
+        }
+    }
+}
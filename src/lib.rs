@@ -0,0 +1,790 @@
+use futures::stream::{Stream, StreamExt};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::borrow::BorrowMut;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+mod address;
+mod config;
+mod error;
+#[cfg(feature = "geoip")]
+mod geoip;
+mod group;
+#[cfg(feature = "icmp")]
+mod icmp;
+mod parse;
+mod ping;
+mod ramp;
+mod schema;
+mod stats;
+mod stream;
+
+pub use config::{PingConfig, PingConfigBuilder};
+pub use error::Error;
+#[cfg(feature = "geoip")]
+pub use geoip::GeoIp;
+#[cfg(feature = "icmp")]
+pub use icmp::IcmpPinger;
+pub use ping::{
+    BothPinger, FallbackPinger, PingCommand, Pinger, Probe, ProbePinger, Replies, SubprocessPinger,
+    Summary, TcpPinger, UdpPinger,
+};
+pub use schema::LobbySchema;
+pub use stats::{PingStats, ProbeResult};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Room {
+    // lobby mirrors don't all list every field. only the address can't be done without,
+    // rooms missing it are dropped after parsing
+    #[serde(default)]
+    pub port: u32,
+    #[serde(default)]
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "preferredGameName", default)]
+    pub game_name: String,
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub players: Vec<Player>,
+    #[serde(rename = "maxPlayers", default)]
+    pub max_players: Option<u32>,
+    #[serde(rename = "hasPassword", default)]
+    pub has_password: bool,
+
+    #[serde(skip)]
+    pub ip: Option<IpAddr>,
+    /// The other addresses the room's hostname resolved to, when resolving them all.
+    /// Once pinged, `ip` is the one that replied quickest.
+    #[serde(skip)]
+    pub other_ips: Vec<IpAddr>,
+    /// The reverse DNS name of `ip`, when asked for and there is one.
+    #[serde(skip)]
+    pub hostname: Option<String>,
+    /// The ISO code of the country `ip` is in, see `GeoIp`.
+    #[serde(skip)]
+    pub country: Option<String>,
+    #[serde(skip)]
+    pub ping: ProbeResult,
+    /// The latency of every reply `ping` sums up, in the order they were received.
+    #[serde(skip)]
+    pub replies: Vec<Duration>,
+    #[serde(skip)]
+    pub packet_loss: Option<f32>,
+    /// Which probe the ping is from, when pinging with more than one.
+    #[serde(skip)]
+    pub probe: Option<Probe>,
+    /// A name of the user's own for the room's address, shown along with the room's.
+    #[serde(skip)]
+    pub label: Option<String>,
+}
+
+impl Room {
+    /// Whether there's no room for another player. Rooms that don't say how many players
+    /// they allow are never full.
+    pub fn is_full(&self) -> bool {
+        self.max_players
+            .is_some_and(|max| self.players.len() >= max as usize)
+    }
+
+    /// Whether any player's nickname contains `query`, ignoring case.
+    pub fn has_player(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.players
+            .iter()
+            .any(|player| player.nickname.to_lowercase().contains(&query))
+    }
+
+    /// Whether the players aren't all running the game the room is hosting, which
+    /// usually means different versions or mods that won't play together. Players who
+    /// aren't running anything are left out.
+    pub fn has_version_mismatch(&self) -> bool {
+        self.players
+            .iter()
+            .filter(|player| !player.game.is_empty())
+            .any(|player| player.game != self.game_name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Player {
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(rename = "gameName", default)]
+    pub game: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Response {
+    pub rooms: Vec<Room>,
+}
+
+impl Response {
+    /// Combine the rooms of several lobbies, see [`Response::dedup`] for rooms listed by
+    /// more than one.
+    pub fn merge(responses: impl IntoIterator<Item = Response>) -> Response {
+        let mut resp = Response {
+            rooms: responses.into_iter().flat_map(|resp| resp.rooms).collect(),
+        };
+        resp.dedup();
+        resp
+    }
+
+    /// Only keep one of the rooms that share an address and port, the one with the most
+    /// players, in place of the first of them.
+    pub fn dedup(&mut self) {
+        let mut kept = HashMap::<(String, u32), usize>::new();
+        let mut rooms = Vec::<Room>::with_capacity(self.rooms.len());
+
+        for room in std::mem::take(&mut self.rooms) {
+            match kept.entry((room.address.clone(), room.port)) {
+                Entry::Occupied(entry) => {
+                    let kept = &mut rooms[*entry.get()];
+                    if room.players.len() > kept.players.len() {
+                        *kept = room;
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(rooms.len());
+                    rooms.push(room);
+                }
+            }
+        }
+
+        self.rooms = rooms;
+    }
+
+    /// Every game hosted in the lobby along with how many rooms are hosting it, most
+    /// hosted first.
+    pub fn games(&self) -> Vec<(&str, usize)> {
+        let mut games = HashMap::<&str, usize>::new();
+        for room in &self.rooms {
+            *games.entry(&room.game_name).or_default() += 1;
+        }
+
+        let mut games = games.into_iter().collect::<Vec<_>>();
+        games.sort_by(|left, right| right.1.cmp(&left.1).then(left.0.cmp(right.0)));
+        games
+    }
+
+    /// Look up which hosted game `query` refers to.
+    ///
+    /// An exact match always wins. Otherwise the query is compared ignoring case and
+    /// punctuation, first against the whole name and then as a part of it, so that both
+    /// `super smash bros ultimate` and `smash` find `Super Smash Bros. Ultimate`.
+    pub fn find_game(&self, query: &str) -> GameMatch {
+        let games = self.games();
+
+        if games.iter().any(|&(game, _)| game == query) {
+            return GameMatch::Found(query.to_string());
+        }
+
+        let query = normalize_game(query);
+
+        let equal = games
+            .iter()
+            .filter(|(game, _)| normalize_game(game) == query)
+            .collect::<Vec<_>>();
+
+        let candidates = if equal.is_empty() {
+            games
+                .iter()
+                .filter(|(game, _)| normalize_game(game).contains(&query))
+                .collect()
+        } else {
+            equal
+        };
+
+        match candidates.as_slice() {
+            [] => GameMatch::None,
+            [(game, _)] => GameMatch::Found(game.to_string()),
+            _ => GameMatch::Ambiguous(
+                candidates
+                    .iter()
+                    .map(|(game, _)| game.to_string())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Merge the rooms that look like one host announcing itself at several addresses: the
+/// same name and game, and mostly the same players. The merged room keeps the address
+/// with the lowest ping along with everyone from either list, so call this after
+/// pinging. Rooms without players are never merged, there'd be nothing to tell them
+/// apart by.
+pub fn merge_mirrored(rooms: Vec<Room>) -> Vec<Room> {
+    let mut merged = Vec::<Room>::with_capacity(rooms.len());
+    let mut by_name = HashMap::<(String, String), Vec<usize>>::new();
+
+    for room in rooms {
+        let key = (room.name.clone(), room.game_name.clone());
+        let candidates = by_name.entry(key).or_default();
+        let mirror = candidates
+            .iter()
+            .copied()
+            .find(|&index| same_players(&merged[index], &room));
+
+        let Some(index) = mirror else {
+            candidates.push(merged.len());
+            merged.push(room);
+            continue;
+        };
+
+        let kept = &mut merged[index];
+        let faster = |room: &Room| room.ping.stats().map(|ping| ping.min);
+        let (mut keep, other) = match (faster(kept), faster(&room)) {
+            (None, Some(_)) => (room, std::mem::take(kept)),
+            (Some(left), Some(right)) if right < left => (room, std::mem::take(kept)),
+            _ => (std::mem::take(kept), room),
+        };
+        tracing::debug!(
+            name = keep.name,
+            kept = keep.address,
+            merged = other.address,
+            "merged"
+        );
+        for player in other.players {
+            if !keep
+                .players
+                .iter()
+                .any(|kept| kept.nickname == player.nickname)
+            {
+                keep.players.push(player);
+            }
+        }
+        *kept = keep;
+    }
+
+    merged
+}
+
+// at least half the players of the bigger room are in both
+fn same_players(left: &Room, right: &Room) -> bool {
+    if left.players.is_empty() || right.players.is_empty() {
+        return false;
+    }
+
+    let both = left
+        .players
+        .iter()
+        .filter(|player| {
+            right
+                .players
+                .iter()
+                .any(|other| other.nickname == player.nickname)
+        })
+        .count();
+    both * 2 >= left.players.len().max(right.players.len())
+}
+
+/// The outcome of [`Response::find_game`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameMatch {
+    Found(String),
+    /// Several games match, most hosted first.
+    Ambiguous(Vec<String>),
+    None,
+}
+
+fn normalize_game(game: &str) -> String {
+    game.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Fetch every room of the lobby at `url`, which speaks `schema`.
+pub async fn fetch_lobby(
+    client: &reqwest::Client,
+    url: &str,
+    schema: LobbySchema,
+) -> Result<Response, Error> {
+    tracing::debug!(url, "fetching the lobby");
+    let json = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    parse_lobby(&json, schema)
+}
+
+/// Read a lobby saved to `path`, in the same format as `schema`'s lobby API responds
+/// with.
+pub fn read_lobby(path: &std::path::Path, schema: LobbySchema) -> Result<Response, Error> {
+    let json = std::fs::read(path)?;
+    parse_lobby(&json, schema)
+}
+
+/// How many chunks of the body may wait on the parser, see [`fetch_lobby_filtered`].
+const CHUNKS_IN_FLIGHT: usize = 8;
+
+/// Fetch the rooms of the lobby at `url` that `keep` returns true for. Unlike
+/// [`fetch_lobby`], the body isn't held in memory in whole and the rooms are filtered
+/// as they're parsed, for big lobbies of which only a few rooms are of interest.
+pub async fn fetch_lobby_filtered(
+    client: &reqwest::Client,
+    url: &str,
+    schema: LobbySchema,
+    keep: impl FnMut(&Room) -> bool + Send + 'static,
+) -> Result<Response, Error> {
+    tracing::debug!(url, "fetching the lobby");
+    let mut resp = client.get(url).send().await?.error_for_status()?;
+
+    let (chunks, received) = tokio::sync::mpsc::channel(CHUNKS_IN_FLIGHT);
+    let parsed = tokio::task::spawn_blocking(move || {
+        schema.parse_filtered(stream::ChunkReader::new(received), keep)
+    });
+
+    let downloaded = async {
+        while let Some(chunk) = resp.chunk().await? {
+            // the parser gave up on the body, and its error says why
+            if chunks.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        Ok::<_, Error>(())
+    }
+    .await;
+    drop(chunks);
+
+    let parsed = parsed.await.expect("parsing the lobby doesn't panic");
+    // a body that was cut short is an error of its own, not a decoding one
+    downloaded?;
+    Ok(without_addressless(parsed?))
+}
+
+/// Read the rooms of a lobby saved to `path` that `keep` returns true for, see
+/// [`fetch_lobby_filtered`].
+pub fn read_lobby_filtered(
+    path: &std::path::Path,
+    schema: LobbySchema,
+    keep: impl FnMut(&Room) -> bool,
+) -> Result<Response, Error> {
+    let file = std::fs::File::open(path)?;
+    Ok(without_addressless(schema.parse_filtered(file, keep)?))
+}
+
+fn parse_lobby(json: &[u8], schema: LobbySchema) -> Result<Response, Error> {
+    Ok(without_addressless(schema.parse(json)?))
+}
+
+fn without_addressless(mut resp: Response) -> Response {
+    let listed = resp.rooms.len();
+    resp.rooms.retain(|room| !room.address.trim().is_empty());
+    let dropped = listed - resp.rooms.len();
+    if dropped > 0 {
+        tracing::warn!("ignoring {dropped} rooms without an address");
+    }
+
+    resp
+}
+
+/// Fetch the yuzu lobby from `url` and keep only the rooms hosting the `game` of
+/// `config`, or all of them when it has none.
+pub async fn fetch_rooms(
+    client: &reqwest::Client,
+    url: &str,
+    config: &PingConfig,
+) -> Result<Vec<Room>, Error> {
+    let game = config.game.clone();
+    let resp = fetch_lobby_filtered(client, url, LobbySchema::Yuzu, move |room| {
+        game.as_ref().is_none_or(|game| room.game_name == *game)
+    })
+    .await?;
+
+    Ok(resp.rooms)
+}
+
+/// Ping every room, at most the `concurrency` of `config` at a time, filling in their
+/// `ip`, `ping` and `packet_loss`. With `reverse_dns` their `hostname` is looked up at
+/// the same time.
+///
+/// A room that doesn't reply at all, or can't be pinged, is tried up to `retries` more
+/// times after a short random delay, in case the replies just happened to get lost.
+/// The rest of `config` is up to `pinger`, see [`PingConfig::pinger`].
+///
+/// `on_done` is called as soon as each room finishes, in the order they finish, along
+/// with the number of rooms done so far and the total.
+///
+/// Once `cancel` is cancelled the pings still running are dropped, killing any `ping`
+/// they spawned, and their rooms are left unpinged. The same goes for when there's no
+/// pinging any room at all, like when `ping` isn't installed, which is returned as an
+/// error.
+pub async fn ping_rooms(
+    rooms: &mut [Room],
+    config: &PingConfig,
+    pinger: &impl Pinger,
+    cancel: &CancellationToken,
+    mut on_done: impl FnMut(&Room, usize, usize),
+) -> Result<(), Error> {
+    let total = rooms.len();
+    let rooms = rooms.iter_mut().collect();
+    let pings = ping_each(rooms, config, pinger);
+    let mut pings = std::pin::pin!(pings);
+
+    let mut done = 0;
+    loop {
+        let room = tokio::select! {
+            room = pings.next() => room,
+            _ = cancel.cancelled() => None,
+        };
+        let Some((room, pinged)) = room else { break };
+        pinged?;
+
+        done += 1;
+        on_done(room, done, total);
+    }
+    Ok(())
+}
+
+/// Ping every room like [`ping_rooms`], yielding each one as soon as it's done.
+///
+/// Nothing is pinged until the stream is polled. Dropping it stops the pings still
+/// running, killing any `ping` they spawned, and the rooms that weren't yielded yet are
+/// dropped along with it. The stream ends early when there's no pinging any room at
+/// all, like when `ping` isn't installed.
+pub fn ping_rooms_stream<'a>(
+    rooms: Vec<Room>,
+    config: &PingConfig,
+    pinger: &'a impl Pinger,
+) -> impl Stream<Item = Room> + 'a {
+    ping_each(rooms, config, pinger).scan((), |(), (room, pinged)| {
+        std::future::ready(match pinged {
+            Ok(()) => Some(room),
+            Err(err) => {
+                tracing::error!("{err}");
+                None
+            }
+        })
+    })
+}
+
+// shared by both of the above, which differ in whether the rooms are owned or borrowed.
+// the rooms are taken from the iterator one at a time as pings finish, so no more than
+// `concurrency` pings exist at once however large the lobby is, the same as a pool of
+// that many workers would but without spawning tasks that need the pinger to be 'static.
+// `cargo bench -- ping` measures both
+fn ping_each<'a, R: BorrowMut<Room> + 'a>(
+    rooms: Vec<R>,
+    config: &PingConfig,
+    pinger: &'a impl Pinger,
+) -> impl Stream<Item = (R, Result<(), Error>)> + 'a {
+    let PingConfig {
+        concurrency,
+        reverse_dns,
+        retries,
+        all_addresses,
+        ..
+    } = *config;
+    let warmup = config.warmup.then_some(config.count);
+    // with an adaptive concurrency, up to `concurrency` pings wait on the ramp instead
+    let ramp = config
+        .adaptive
+        .then(|| Arc::new(ramp::Ramp::new(concurrency)));
+    futures::stream::once(resolve_all(rooms, all_addresses)).flat_map(move |rooms| {
+        let ramp = ramp.clone();
+        futures::stream::iter(rooms)
+            .map(move |mut room| {
+                let ramp = ramp.clone();
+                async move {
+                    let permit = match &ramp {
+                        Some(ramp) => Some(ramp.acquire().await),
+                        None => None,
+                    };
+                    // tokio's, so that the ramp keeps to the clock of the runtime
+                    let started = tokio::time::Instant::now();
+                    let room_mut = room.borrow_mut();
+                    let pinged = ping_room(room_mut, pinger, reverse_dns, retries, warmup).await;
+                    if let (Some(ramp), Some(permit)) = (&ramp, permit) {
+                        let timed_out = room_mut.ping == ProbeResult::Timeout;
+                        ramp.release(permit, started.elapsed(), timed_out);
+                    }
+                    (room, pinged)
+                }
+            })
+            .buffer_unordered(concurrency)
+    })
+}
+
+/// How many hostnames to resolve at the same time.
+const RESOLVE_CONCURRENCY: usize = 8;
+
+/// Look up the `ip` of every room ahead of [`ping_rooms`], which otherwise does so
+/// itself, to tell how long it took apart from the pinging, along with their
+/// `other_ips` with the `all_addresses` of `config`. Rooms that already have an `ip` are
+/// left as they are.
+pub async fn resolve_rooms(rooms: &mut [Room], config: &PingConfig) {
+    resolve_all(rooms.iter_mut().collect(), config.all_addresses).await;
+}
+
+// resolving every host before pinging, and only once no matter how many rooms it has,
+// keeps slow DNS from holding up the pings. any room left without an `ip` isn't pinged
+async fn resolve_all<R: BorrowMut<Room>>(mut rooms: Vec<R>, all: bool) -> Vec<R> {
+    let mut hosts = HashSet::new();
+    let pending = rooms
+        .iter_mut()
+        .map(|room| {
+            let room = room.borrow_mut();
+            if room.ip.is_some() {
+                return false;
+            }
+            match address::normalize(&room.address) {
+                Ok((address, port)) => {
+                    room.address = address;
+                    if let Some(port) = port {
+                        room.port = port.into();
+                    }
+                    hosts.insert(room.address.clone());
+                    true
+                }
+                Err(reason) => {
+                    tracing::warn!("not pinging {} at {:?}: {reason}", room.name, room.address);
+                    room.ping = ProbeResult::Error(reason.to_string());
+                    false
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let ips = futures::stream::iter(hosts)
+        .map(|host| async move {
+            match resolve_every(&host).await {
+                Ok(mut ips) => {
+                    tracing::debug!(address = %host, ?ips, "resolved");
+                    if !all {
+                        ips.truncate(1);
+                    }
+                    Some((host, ips))
+                }
+                Err(err) => {
+                    tracing::debug!("unable to resolve {host}: {err}");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(RESOLVE_CONCURRENCY)
+        .filter_map(std::future::ready)
+        .collect::<HashMap<_, _>>()
+        .await;
+
+    let mut unresolved = 0;
+    for (room, _) in rooms
+        .iter_mut()
+        .zip(pending)
+        .filter(|(_, pending)| *pending)
+    {
+        let room = room.borrow_mut();
+        match ips.get(&room.address).map(Vec::as_slice) {
+            Some([ip, others @ ..]) => {
+                room.ip = Some(*ip);
+                room.other_ips = others.to_vec();
+            }
+            _ => unresolved += 1,
+        }
+    }
+    if unresolved > 0 {
+        tracing::warn!("{unresolved} rooms couldn't be resolved and won't be pinged");
+    }
+
+    rooms
+}
+
+/// Ping `room` and record how it went. Only fails when no other room could be pinged
+/// either, the rest is logged. With `warmup`, the first reply is left out of the rest,
+/// which answer that many probes.
+async fn ping_room(
+    room: &mut Room,
+    pinger: &impl Pinger,
+    reverse_dns: bool,
+    retries: u32,
+    warmup: Option<u8>,
+) -> Result<(), Error> {
+    let Some(ip) = room.ip else {
+        // the address was already found to be unusable
+        if room.ping == ProbeResult::Pending {
+            room.ping = ProbeResult::ResolveFailed;
+        }
+        return Ok(());
+    };
+
+    // a port that doesn't fit is garbage from the lobby, and will fail to connect
+    let port = u16::try_from(room.port).unwrap_or_default();
+
+    let ips = std::iter::once(ip)
+        .chain(room.other_ips.iter().copied())
+        .collect::<Vec<_>>();
+    let ((ip, result), hostname) =
+        tokio::join!(ping_quickest(pinger, &ips, port, retries), async {
+            if reverse_dns {
+                reverse_resolve(ip).await
+            } else {
+                None
+            }
+        });
+    room.hostname = hostname;
+    if ips.len() > 1 {
+        tracing::debug!(%ip, tried = ips.len(), "quickest of the addresses");
+        room.ip = Some(ip);
+        room.other_ips = ips.into_iter().filter(|&other| other != ip).collect();
+    }
+
+    let result = match warmup {
+        Some(count) => result.map(|replies| replies.without_warmup(count)),
+        None => result,
+    };
+    match result {
+        Ok(replies) => {
+            let stats = PingStats::from_replies(&replies.latencies).map(|stats| {
+                // rounded the same as the replies, but without having to find them first
+                match replies.summary {
+                    Some(summary) => PingStats {
+                        // windows rounds replies under a millisecond down to 0ms
+                        min: if summary.min.is_zero() {
+                            stats.min
+                        } else {
+                            summary.min
+                        },
+                        avg: summary.avg,
+                        max: summary.max,
+                        ..stats
+                    },
+                    None => stats,
+                }
+            });
+            room.ping = match stats {
+                Some(stats) => ProbeResult::Reachable(stats),
+                None if replies.unreachable => ProbeResult::Unreachable,
+                None => ProbeResult::Timeout,
+            };
+            room.packet_loss = replies.packet_loss;
+            room.probe = replies.probe;
+            room.replies = replies.latencies;
+            tracing::debug!(%ip, ping = ?room.ping, packet_loss = ?room.packet_loss, "pinged");
+        }
+        Err(err @ Error::PingNotFound(_)) => return Err(err),
+        // given up on, which is no different from none of the probes being answered
+        Err(Error::Ping(err)) if err.kind() == std::io::ErrorKind::TimedOut => {
+            tracing::debug!(%ip, "{err}");
+            room.ping = ProbeResult::Timeout;
+            room.packet_loss = Some(100.0);
+        }
+        Err(err) => {
+            tracing::warn!("{err}");
+            room.ping = ProbeResult::Error(err.to_string());
+        }
+    }
+    Ok(())
+}
+
+// one address after the other, so that a room with several still only takes up one of
+// the pings at a time
+async fn ping_quickest(
+    pinger: &impl Pinger,
+    ips: &[IpAddr],
+    port: u16,
+    retries: u32,
+) -> (IpAddr, Result<Replies, Error>) {
+    let mut quickest: Option<(IpAddr, Result<Replies, Error>)> = None;
+    for &ip in ips {
+        let result = ping_with_retries(pinger, ip, port, retries).await;
+        if matches!(result, Err(Error::PingNotFound(_))) {
+            return (ip, result);
+        }
+
+        // any reply beats none, which beats failing to ping at all
+        let rank = |result: &Result<Replies, Error>| match result {
+            Ok(replies) => match replies.latencies.iter().min() {
+                Some(&min) => (0, min),
+                None => (1, Duration::ZERO),
+            },
+            Err(_) => (2, Duration::ZERO),
+        };
+        if quickest
+            .as_ref()
+            .is_none_or(|(_, quickest)| rank(&result) < rank(quickest))
+        {
+            quickest = Some((ip, result));
+        }
+    }
+    quickest.expect("there's always at least one address")
+}
+
+async fn ping_with_retries(
+    pinger: &impl Pinger,
+    ip: IpAddr,
+    port: u16,
+    retries: u32,
+) -> Result<Replies, Error> {
+    let mut attempt = 0;
+    loop {
+        let result = pinger.ping(&ip, port).await;
+        let replied = result
+            .as_ref()
+            .is_ok_and(|replies| !replies.latencies.is_empty());
+        let hopeless = matches!(result, Err(Error::PingNotFound(_)));
+        if replied || hopeless || attempt == retries {
+            return result;
+        }
+
+        attempt += 1;
+        // spread out so that rooms that failed together aren't retried together
+        let delay = Duration::from_millis(rand::thread_rng().gen_range(100..400));
+        tracing::debug!(%ip, ?delay, "no replies, retrying ({attempt}/{retries})");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Resolve an address written the way the lobby lists them, like `example.com:24872`,
+/// to an IP along with the port it carried if any.
+pub async fn lookup(address: &str) -> Result<(IpAddr, Option<u16>), Error> {
+    let (host, port) = address::normalize(address)
+        .map_err(|reason| std::io::Error::new(std::io::ErrorKind::InvalidInput, reason))?;
+    Ok((resolve(&host).await?, port))
+}
+
+// some rooms list a hostname instead of an IP, but ping only ever echoes back the
+// address it resolved to. resolving up-front also gives us the canonical form of IPv6
+// addresses to match against.
+async fn resolve(address: &str) -> std::io::Result<IpAddr> {
+    let ips = resolve_every(address).await?;
+    Ok(ips[0])
+}
+
+// every address once, in the order the resolver gave them. never empty
+async fn resolve_every(address: &str) -> std::io::Result<Vec<IpAddr>> {
+    if let Ok(ip) = address.parse() {
+        return Ok(vec![ip]);
+    }
+
+    let mut ips = Vec::new();
+    for addr in tokio::net::lookup_host((address, 0)).await? {
+        if !ips.contains(&addr.ip()) {
+            ips.push(addr.ip());
+        }
+    }
+    if ips.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no addresses found",
+        ));
+    }
+    Ok(ips)
+}
+
+// the system resolver blocks, so it gets a thread of its own
+async fn reverse_resolve(ip: IpAddr) -> Option<String> {
+    let hostname = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&ip))
+        .await
+        .ok()?
+        .ok()?;
+    tracing::debug!(%ip, hostname, "reverse resolved");
+    Some(hostname)
+}
@@ -1,154 +1,1688 @@
-use futures::future::join_all;
-use serde::{Deserialize, Serialize};
-use std::future::Future;
-use std::sync::atomic::AtomicU64;
-use std::sync::atomic::Ordering;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio;
-use tokio::sync::Semaphore;
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Room {
-    port: u32,
-    name: String,
-    description: Option<String>,
-    #[serde(rename = "preferredGameName")]
-    game_name: String,
-    address: String,
-    players: Vec<Player>,
-
-    #[serde(skip)]
-    ping: Option<Duration>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Player {
-    nickname: String,
-    #[serde(rename = "gameName")]
-    game: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Response {
-    rooms: Vec<Room>,
-}
-
-#[tokio::main]
-async fn main() {
-    let url = std::env::var("YUZU_LOBBY_URL")
-        .unwrap_or_else(|_| String::from("https://api.yuzu-emu.org/lobby"));
-
-    let game_name = std::env::var("YUZU_GAME_NAME")
-        .unwrap_or_else(|_| String::from("Super Smash Bros. Ultimate"));
-
-    let resp = reqwest::get(url)
-        .await
-        .unwrap()
-        .json::<Response>()
-        .await
-        .unwrap();
-
-    let mut rooms = resp
-        .rooms
-        .into_iter()
-        .filter(|room| room.game_name == game_name)
-        .collect::<Vec<_>>();
+use allowlist::AddressList;
+use anonymize::Anonymizer;
+use base64::Engine;
+use cache::{Cache, LobbyCache};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use cli::{Args, Concurrency, Format, ProbeArg, SortKey, Stat};
+use favorites::Favorites;
+use history::History;
+use indicatif::{HumanDuration, ProgressBar, ProgressState, ProgressStyle};
+use labels::Labels;
+use output::{Colors, JsonMeta, JsonOptions, PlainOptions};
+use pick::Action;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio_util::sync::CancellationToken;
+use yuzuping::{
+    fetch_lobby, lookup, merge_mirrored, ping_rooms, read_lobby, Error, FallbackPinger, GameMatch,
+    LobbySchema, PingConfig, PingStats, Pinger, ProbePinger, Response, Room,
+};
 
-    let semaphore = Arc::new(Semaphore::new(10));
+mod allowlist;
+mod anonymize;
+mod cache;
+mod cli;
+mod diff;
+mod favorites;
+mod history;
+mod ipc;
+mod labels;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod notify;
+mod output;
+mod pick;
+mod template;
 
-    let total = rooms.len();
-    let count = AtomicU64::new(0);
+/// Exit codes for scripts to tell why nothing came of the run. 2 is clap's for invalid
+/// arguments.
+const FAILED: u8 = 1;
+const FETCH_FAILED: u8 = 3;
+const NO_MATCHING_ROOMS: u8 = 4;
+const NONE_REACHABLE: u8 = 5;
 
-    let pings = rooms.iter_mut().map(|room| async {
-        let _permit = semaphore.clone().acquire_owned().await.unwrap();
+/// Exit codes of --check, the ones nagios plugins exit with.
+const CHECK_WARNING: u8 = 1;
+const CHECK_CRITICAL: u8 = 2;
+const CHECK_UNKNOWN: u8 = 3;
 
-        let c = count.load(Ordering::Relaxed);
-        println!("{}/{}", c, total);
-        count.store(c + 1, Ordering::Relaxed);
+/// The game shown without --game, when there's no one to ask.
+const DEFAULT_GAME: &str = "Super Smash Bros. Ultimate";
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.green_below >= args.yellow_below {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--green-below has to be lower than --yellow-below",
+            )
+            .exit();
+    }
+
+    // RUST_LOG takes precedence over --quiet, for debugging
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        tracing_subscriber::EnvFilter::new(if args.quiet { "warn" } else { "info" })
+    });
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none())
+        .without_time()
+        .with_target(false)
+        .init();
+
+    // the work is mostly waiting on pings, so one thread is plenty when they're done one
+    // at a time
+    let runtime = match args.threads {
+        None if args.concurrency == Concurrency::Fixed(1) => {
+            tokio::runtime::Builder::new_current_thread()
+        }
+        Some(1) => tokio::runtime::Builder::new_current_thread(),
+        Some(threads) => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            builder.worker_threads(threads);
+            builder
+        }
+        None => tokio::runtime::Builder::new_multi_thread(),
+    }
+    .enable_all()
+    .build();
+    let runtime = match runtime {
+        Ok(runtime) => runtime,
+        Err(err) => return fail(&args, &format!("unable to start: {err}"), FAILED),
+    };
 
-        match ping(&room.address).await {
-            Ok(output) => room.ping = output_to_duration(room.address.as_bytes(), output),
-            Err(err) => eprintln!("unable to ping: {err}"),
+    // ctrl-c stops the pinging, after which the rooms pinged so far are still printed
+    let cancel = CancellationToken::new();
+    runtime.spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
         }
     });
 
-    join_all(pings).await;
+    let status = match runtime.block_on(run(&args, &cancel)) {
+        Ok(status) => status,
+        Err(Error::Http(err)) if err.is_timeout() => fail(
+            &args,
+            "the yuzu lobby took too long to respond, try raising --http-timeout",
+            FETCH_FAILED,
+        ),
+        Err(Error::Http(err)) => fail(
+            &args,
+            &format!("couldn't reach the yuzu lobby, is --url correct?\n{err}"),
+            FETCH_FAILED,
+        ),
+        Err(err @ Error::Decode(_)) => fail(&args, &lobby_error(&args, &err), FETCH_FAILED),
+        Err(err @ Error::PingNotFound(_)) => fail(
+            &args,
+            &format!("{err}, install it or set --ping-cmd to ping with something else"),
+            FAILED,
+        ),
+        Err(err) => fail(&args, &err.to_string(), FAILED),
+    };
 
-    rooms.sort_by(|left, right| none_is_high(&left.ping).cmp(&none_is_high(&right.ping)));
+    // keep the window open for those who double-clicked the executable, unless they
+    // already asked to quit
+    let pause = if args.pause {
+        true
+    } else {
+        !args.no_pause
+            && args.is_interactive()
+            && args.refresh_every().is_none()
+            && std::io::stdout().is_terminal()
+            && own_console()
+    };
+    if pause && !cancel.is_cancelled() {
+        println!(" - press enter to exit - ");
 
-    for room in rooms.iter().rev() {
-        if let Some(ping) = room.ping {
-            println!(
-                "{} ({} playing)  {:?}",
-                &room.name,
-                room.players.len(),
-                ping
-            );
+        // there's nothing left to do if stdin is gone
+        let _ = std::io::stdin().read_line(&mut String::new());
+    }
+
+    status
+}
+
+/// Whether the console was opened for us alone, as it is when the executable is started
+/// from explorer rather than from a shell that stays behind once we exit.
+#[cfg(windows)]
+fn own_console() -> bool {
+    let mut processes = [0u32; 2];
+    // SAFETY: the list is valid for writes of as many ids as its length
+    let attached = unsafe {
+        windows_sys::Win32::System::Console::GetConsoleProcessList(
+            processes.as_mut_ptr(),
+            processes.len() as u32,
+        )
+    };
+    attached == 1
+}
+
+/// Terminal windows don't close with the programs started in them outside of windows.
+#[cfg(not(windows))]
+fn own_console() -> bool {
+    false
+}
+
+async fn run(args: &Args, cancel: &CancellationToken) -> Result<ExitCode, Error> {
+    if !args.add_favorite.is_empty() || !args.remove_favorite.is_empty() {
+        return Ok(edit_favorites(args));
+    }
+    if let Some([old, new]) = args.diff.as_deref() {
+        return Ok(compare_lobbies(args, old, new));
+    }
+
+    let proxy = match args.proxy.as_deref().map(reqwest::Proxy::all).transpose() {
+        Ok(proxy) => proxy,
+        Err(err) => {
+            let proxy = args.proxy.as_deref().unwrap_or_default();
+            return Ok(fail(
+                args,
+                &format!("invalid --proxy {proxy}: {err}"),
+                FAILED,
+            ));
+        }
+    };
+    let auth = match lobby_auth(args) {
+        Ok(auth) => auth,
+        Err(message) => return Ok(fail(args, &message, FAILED)),
+    };
+
+    // the timeout covers the whole request, including reading the body. compressed
+    // responses are asked for and decoded transparently
+    let http_client = |auth: Option<HeaderValue>| {
+        let mut client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(args.http_timeout))
+            .user_agent(&args.user_agent)
+            .gzip(true)
+            .brotli(true)
+            .deflate(true);
+        if let Some(auth) = auth {
+            client = client.default_headers(HeaderMap::from_iter([(AUTHORIZATION, auth)]));
+        }
+        // without --proxy, reqwest picks up the proxy environment variables by itself,
+        // and connects directly when there are none
+        if let Some(proxy) = &proxy {
+            client = client.proxy(proxy.clone());
+        }
+        client.build()
+    };
+    // the lobby's credentials aren't the webhook's business
+    let webhook_client = match (&auth, &args.webhook) {
+        (Some(_), Some(_)) => Some(http_client(None)?),
+        _ => None,
+    };
+    let client = http_client(auth)?;
+
+    let config = args.ping_config();
+    if args.ping_cmd.is_some() && !matches!(args.probe, ProbeArg::Icmp | ProbeArg::Both) {
+        tracing::warn!("--ping-cmd is only used by --probe icmp and both");
+    }
+    let pinger = match config.pinger() {
+        Ok(pinger) => pinger,
+        Err(err) => {
+            return Ok(fail(
+                args,
+                &format!("unable to set up the {:?} probe: {err}", args.probe),
+                FAILED,
+            ));
+        }
+    };
+
+    if let Some(address) = &args.monitor {
+        return monitor(address, args.count, &pinger, cancel).await;
+    }
+
+    // read up front, since stdin only has them the once
+    let given = if args.stdin {
+        match std::io::read_to_string(std::io::stdin()) {
+            Ok(addresses) => Some(rooms_at(&addresses)),
+            Err(err) => {
+                return Ok(fail(
+                    args,
+                    &format!("unable to read the addresses from stdin: {err}"),
+                    FAILED,
+                ));
+            }
+        }
+    } else {
+        None
+    };
+
+    let escalation = match args.timeout_escalation {
+        Some(timeout) => {
+            let config = PingConfig {
+                timeout: Duration::from_millis(timeout),
+                ..config.clone()
+            };
+            match config.pinger() {
+                Ok(pinger) => Some((config, pinger)),
+                Err(err) => {
+                    let message = format!("unable to set up the {:?} probe: {err}", args.probe);
+                    return Ok(fail(args, &message, FAILED));
+                }
+            }
+        }
+        None => None,
+    };
+
+    let session = Session {
+        client: client.clone(),
+        config,
+        pinger,
+        #[cfg(feature = "geoip")]
+        geoip: match &args.geoip_db {
+            Some(path) => match yuzuping::GeoIp::open(path) {
+                Ok(geoip) => Some(geoip),
+                Err(err) => {
+                    return Ok(fail(
+                        args,
+                        &format!("unable to read {}: {err}", path.display()),
+                        FAILED,
+                    ));
+                }
+            },
+            None => None,
+        },
+        webhook: args.webhook.clone().map(notify::Webhook::new),
+        webhook_client: webhook_client.unwrap_or_else(|| client.clone()),
+        ipc: match &args.ipc {
+            Some(path) => match ipc::Ipc::listen(path) {
+                Ok(ipc) => Some(ipc),
+                Err(err) => {
+                    return Ok(fail(
+                        args,
+                        &format!("unable to listen at {}: {err}", path.display()),
+                        FAILED,
+                    ));
+                }
+            },
+            None => None,
+        },
+        #[cfg(feature = "metrics")]
+        metrics: match args.metrics_addr {
+            Some(addr) => match metrics::Metrics::serve(addr).await {
+                Ok(metrics) => Some(metrics),
+                Err(err) => {
+                    return Ok(fail(
+                        args,
+                        &format!("unable to serve the metrics on {addr}: {err}"),
+                        FAILED,
+                    ));
+                }
+            },
+            None => None,
+        },
+        given,
+        picked_game: OnceLock::new(),
+        picked_matches: Mutex::new(HashMap::new()),
+        exclude: match &args.exclude {
+            Some(path) => match AddressList::read(path) {
+                Ok(list) => Some(list),
+                Err(err) => {
+                    return Ok(fail(
+                        args,
+                        &format!("unable to read {}: {err}", path.display()),
+                        FAILED,
+                    ));
+                }
+            },
+            None => None,
+        },
+        include_only: match &args.include_only {
+            Some(path) => match AddressList::read(path) {
+                Ok(list) => Some(list),
+                Err(err) => {
+                    return Ok(fail(
+                        args,
+                        &format!("unable to read {}: {err}", path.display()),
+                        FAILED,
+                    ));
+                }
+            },
+            None => None,
+        },
+        escalation,
+        history: (args.refresh_every().is_some() && !args.no_ping)
+            .then(|| History::new(args.history)),
+        anonymizer: args.anonymize.then(Anonymizer::default),
+        labels: match &args.labels {
+            Some(path) => match Labels::read(path) {
+                Ok(labels) => Some(labels),
+                Err(err) => {
+                    return Ok(fail(
+                        args,
+                        &format!("unable to read {}: {err}", path.display()),
+                        FAILED,
+                    ));
+                }
+            },
+            None => None,
+        },
+    };
+
+    if args.every_game() {
+        tracing::warn!(
+            "the game to show is blank, so there's no game filter and every game is shown"
+        );
+    }
+
+    let Some(interval) = args.refresh_every() else {
+        return refresh(args, &session, cancel).await;
+    };
+
+    loop {
+        let refreshed = refresh(args, &session, cancel).await;
+        let wait = if args.loop_every.is_some() {
+            // only a missing ping stays missing however long it's waited for
+            match refreshed {
+                Err(err @ Error::PingNotFound(_)) => return Err(err),
+                Err(err) => {
+                    tracing::warn!("{err}, trying again in {}s", args.loop_backoff);
+                    Duration::from_secs(args.loop_backoff)
+                }
+                Ok(status) if status == ExitCode::from(FETCH_FAILED) => {
+                    Duration::from_secs(args.loop_backoff)
+                }
+                Ok(_) => interval,
+            }
+        } else {
+            let status = refreshed?;
+
+            // rooms may well show up by the next refresh
+            let empty = [NO_MATCHING_ROOMS, NONE_REACHABLE].map(ExitCode::from);
+            let keep_watching =
+                status == ExitCode::SUCCESS || (empty.contains(&status) && !args.fail_fast);
+            if !keep_watching || cancel.is_cancelled() {
+                return Ok(status);
+            }
+            interval
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = cancel.cancelled() => return Ok(ExitCode::SUCCESS),
+        }
+    }
+}
+
+/// How many of the latest probes --monitor sums up.
+const MONITOR_WINDOW: usize = 100;
+
+/// yuzu's default port, for addresses given by hand without one.
+const DEFAULT_PORT: u16 = 24872;
+
+async fn monitor(
+    address: &str,
+    count: u8,
+    pinger: &impl Pinger,
+    cancel: &CancellationToken,
+) -> Result<ExitCode, Error> {
+    let (ip, port) = lookup(address).await?;
+    let port = port.unwrap_or(DEFAULT_PORT);
+    let tty = std::io::stdout().is_terminal();
+
+    // a lost probe is a None, so that the loss is over the same probes as the latencies
+    let mut probes = VecDeque::<Option<Duration>>::with_capacity(MONITOR_WINDOW);
+    while !cancel.is_cancelled() {
+        let started = Instant::now();
+        let replies = tokio::select! {
+            replies = pinger.ping(&ip, port) => replies,
+            _ = cancel.cancelled() => break,
+        };
+
+        match replies {
+            Ok(replies) => {
+                let lost = (count as usize).saturating_sub(replies.latencies.len());
+                probes.extend(replies.latencies.into_iter().map(Some));
+                probes.extend(std::iter::repeat_n(None, lost));
+                while probes.len() > MONITOR_WINDOW {
+                    probes.pop_front();
+                }
+                output::print_monitor(address, &probes, tty);
+            }
+            Err(err @ Error::PingNotFound(_)) => return Err(err),
+            Err(err) => tracing::warn!("{err}"),
+        }
+
+        // the tcp and udp probes are done in no time, and shouldn't hammer the room
+        let rest = Duration::from_secs(1).saturating_sub(started.elapsed());
+        tokio::select! {
+            _ = tokio::time::sleep(rest) => {}
+            _ = cancel.cancelled() => break,
         }
     }
 
-    println!(" - press enter to exit - ");
+    if tty {
+        println!();
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-    std::io::stdin().read_line(&mut String::new()).unwrap();
+/// The Authorization header to fetch the lobby with, from --auth-basic or --auth-bearer.
+/// Marked sensitive, so that it doesn't end up in reqwest's logs.
+fn lobby_auth(args: &Args) -> Result<Option<HeaderValue>, String> {
+    let (value, what) = match (&args.auth_basic, &args.auth_bearer) {
+        (Some(credentials), _) => {
+            let credentials = base64::engine::general_purpose::STANDARD.encode(credentials);
+            (format!("Basic {credentials}"), "--auth-basic")
+        }
+        (None, Some(token)) => (format!("Bearer {}", token.trim()), "--auth-bearer"),
+        (None, None) => return Ok(None),
+    };
+    match HeaderValue::from_str(&value) {
+        Ok(mut value) => {
+            value.set_sensitive(true);
+            Ok(Some(value))
+        }
+        // without the error, which would only repeat the secret
+        Err(_) => Err(format!("invalid {what}, it can't be sent as a header")),
+    }
 }
 
-fn none_is_high(dur: &Option<Duration>) -> Duration {
-    dur.unwrap_or_else(|| Duration::from_secs(1000))
+/// Move `n` of the rooms picked at random to the front, keeping their order. The same
+/// `seed` picks the same rooms out of the same lobby.
+fn sample(rooms: &mut [Room], n: usize, seed: Option<u64>) {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut picked = rand::seq::index::sample(&mut rng, rooms.len(), n).into_vec();
+    picked.sort_unstable();
+    // every room before `index` that was picked has already been moved in front of `i`
+    for (i, index) in picked.into_iter().enumerate() {
+        rooms.swap(i, index);
+    }
 }
 
-// we spawn a subshell instead of using ICMP directly because then we don't require
-// sudo/administrator or setcap.
+/// What stays the same between refreshes.
+struct Session {
+    client: reqwest::Client,
+    config: PingConfig,
+    pinger: FallbackPinger<ProbePinger, ProbePinger>,
+    #[cfg(feature = "geoip")]
+    geoip: Option<yuzuping::GeoIp>,
+    webhook: Option<notify::Webhook>,
+    /// The same as `client`, unless that one sends the lobby's credentials along.
+    webhook_client: reqwest::Client,
+    #[cfg(feature = "metrics")]
+    metrics: Option<metrics::Metrics>,
+    /// Where each refresh is streamed to with --ipc.
+    ipc: Option<ipc::Ipc>,
+    /// The rooms given with --stdin, which are pinged instead of the lobby's.
+    given: Option<Vec<Room>>,
+    /// The game picked at the first refresh, when --game wasn't given.
+    picked_game: OnceLock<String>,
+    /// The game picked for each --game that could mean several, so that watching only
+    /// asks once.
+    picked_matches: Mutex<HashMap<String, String>>,
+    exclude: Option<AddressList>,
+    include_only: Option<AddressList>,
+    labels: Option<Labels>,
+    anonymizer: Option<Anonymizer>,
+    /// The pinger for the rooms that didn't reply, with the timeout of
+    /// --timeout-escalation.
+    escalation: Option<(PingConfig, FallbackPinger<ProbePinger, ProbePinger>)>,
+    /// The pings of the latest refreshes, only while watching.
+    history: Option<History>,
+}
 
-#[cfg(windows)]
-fn ping(address: &str) -> impl Future<Output = Result<std::process::Output, std::io::Error>> {
-    tokio::process::Command::new("ping")
-        .arg("-n")
-        .arg("3")
-        .arg("-w")
-        .arg("500")
-        .arg(address)
-        .output()
+// a room for each address, named after it. the port is split off when resolving
+fn rooms_at(addresses: &str) -> Vec<Room> {
+    addresses
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|address| Room {
+            port: DEFAULT_PORT.into(),
+            name: address.to_string(),
+            address: address.to_string(),
+            ..Room::default()
+        })
+        .collect()
 }
-#[cfg(not(windows))]
-fn ping(address: &str) -> impl Future<Output = Result<std::process::Output, std::io::Error>> {
-    tokio::process::Command::new("ping")
-        .arg("-c")
-        .arg("3")
-        .arg("-W")
-        .arg("0.5")
-        .arg(address)
-        .output()
-}
-
-fn output_to_duration(ip: &[u8], output: std::process::Output) -> Option<Duration> {
-    output
-        .stdout
-        .split(|&b| b == b'\n')
-        .filter(|line| line.windows(ip.len()).any(|window| window == ip))
-        .filter_map(|line| {
-            line.windows(16).find_map(|window| {
-                window.ends_with(b"ms").then(|| {
-                    let start = window
-                        .iter()
-                        .enumerate()
-                        .rev()
-                        .find_map(|(i, &b)| (b == b'=').then_some(i))
-                        .expect("ms line doesn't contain equals")
-                        + 1;
-
-                    let end = window[start..]
-                        .iter()
-                        .position(|n| !n.is_ascii_digit())
-                        .unwrap();
-
-                    std::str::from_utf8(&window[start..start + end])
-                        .unwrap()
-                        .parse()
-                        .unwrap()
+
+// done before pinging so that the rooms skipped aren't probed at all. the networks on
+// the lists are matched against what the addresses resolve to
+async fn skip_listed(session: &Session, rooms: &mut Vec<Room>) {
+    let lists = [&session.exclude, &session.include_only];
+    if lists.iter().all(|list| list.is_none()) {
+        return;
+    }
+
+    let ips = if lists
+        .iter()
+        .flat_map(|list| list.as_ref())
+        .any(AddressList::has_networks)
+    {
+        let lookups = rooms
+            .iter()
+            .map(|room| async { lookup(&room.address).await.ok().map(|(ip, _)| ip) });
+        futures::future::join_all(lookups).await
+    } else {
+        vec![None; rooms.len()]
+    };
+
+    let listed = rooms.len();
+    let mut ips = ips.into_iter();
+    rooms.retain(|room| {
+        let ip = ips.next().flatten();
+        let excluded = session
+            .exclude
+            .as_ref()
+            .is_some_and(|list| list.contains(&room.address, ip));
+        let included = session
+            .include_only
+            .as_ref()
+            .is_none_or(|list| list.contains(&room.address, ip));
+        included && !excluded
+    });
+
+    let skipped = listed - rooms.len();
+    if skipped > 0 {
+        tracing::info!("skipped {skipped} rooms by --exclude or --include-only");
+    }
+}
+
+async fn refresh(
+    args: &Args,
+    session: &Session,
+    cancel: &CancellationToken,
+) -> Result<ExitCode, Error> {
+    let tty = std::io::stdout().is_terminal();
+    let started = Instant::now();
+    let (mut resp, fetched_at) = match (&session.given, &args.from_file) {
+        (Some(rooms), _) => (
+            Response {
+                rooms: rooms.clone(),
+            },
+            SystemTime::now(),
+        ),
+        // a saved lobby was fetched around when it was saved
+        (None, Some(path)) => match read_lobby(path, args.schema.into()) {
+            Ok(resp) => {
+                let saved_at = std::fs::metadata(path).and_then(|meta| meta.modified());
+                (resp, saved_at.unwrap_or_else(|_| SystemTime::now()))
+            }
+            Err(err) => {
+                return Ok(fail(
+                    args,
+                    &format!(
+                        "unable to read the lobby from {}: {}",
+                        path.display(),
+                        lobby_error(args, &err)
+                    ),
+                    FETCH_FAILED,
+                ));
+            }
+        },
+        (None, None) => match cached_lobby(args) {
+            Some(cached) => cached.into_lobby(),
+            None => {
+                let resp = tokio::select! {
+                    resp = fetch_all(args, &session.client) => resp?,
+                    _ = cancel.cancelled() => return Ok(ExitCode::SUCCESS),
+                };
+                let cache = LobbyCache::new(&args.url, SystemTime::now(), resp);
+                if !args.no_cache && args.cache_ttl > 0 {
+                    if let Err(err) = cache.save() {
+                        tracing::warn!("unable to save the lobby for next time: {err}");
+                    }
+                }
+                cache.into_lobby()
+            }
+        },
+    };
+
+    let fetching = started.elapsed();
+
+    if let Some(path) = &args.dump {
+        let json = match &session.anonymizer {
+            Some(anonymizer) => {
+                let mut rooms = resp.rooms.clone();
+                anonymizer.apply(&mut rooms);
+                serde_json::to_vec(&Response { rooms })
+            }
+            None => serde_json::to_vec(&resp),
+        }
+        .expect("the lobby is always serializable");
+        if let Err(err) = std::fs::write(path, json) {
+            tracing::warn!("unable to save the lobby to {}: {err}", path.display());
+        }
+    }
+
+    // so that rooms listed twice aren't pinged and printed twice
+    resp.dedup();
+    let listed = resp.rooms.len();
+
+    // read again every refresh, to pick up favorites added while watching
+    let favorites = if args.favorites {
+        match Favorites::load() {
+            Ok(favorites) if favorites.is_empty() => {
+                return Ok(fail(
+                    args,
+                    "there are no favorites yet, save some with --add-favorite",
+                    FAILED,
+                ));
+            }
+            Ok(favorites) => Some(favorites),
+            Err(err) => {
+                return Ok(fail(
+                    args,
+                    &format!("unable to read the favorites: {err}"),
+                    FAILED,
+                ));
+            }
+        }
+    } else {
+        None
+    };
+    let offline = favorites.as_ref().map_or(Vec::new(), |favorites| {
+        let listed = resp
+            .rooms
+            .iter()
+            .map(|room| room.address.as_str())
+            .collect::<Vec<_>>();
+        favorites
+            .missing(&listed)
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    });
+
+    if args.list_games {
+        for (game, rooms) in resp.games() {
+            println!("{rooms:>4}  {}", output::clean(game));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // the addresses given by hand aren't hosting any game in particular
+    let every_game = args.every_game() || args.stdin;
+    let queries = if every_game {
+        Vec::new()
+    } else if !args.game.is_empty() {
+        args.games()
+    } else if let Some(game) = session.picked_game.get() {
+        vec![game.clone()]
+    } else {
+        match ask_for_game(&resp) {
+            Ok(Some(game)) => vec![session.picked_game.get_or_init(|| game).clone()],
+            Ok(None) => return Ok(ExitCode::SUCCESS),
+            Err(err) => return Ok(fail(args, &err.to_string(), FAILED)),
+        }
+    };
+
+    let mut games = Vec::new();
+    if every_game {
+        games.extend(resp.games().into_iter().map(|(game, _)| game.to_string()));
+    }
+    for query in &queries {
+        match resp.find_game(query) {
+            GameMatch::Found(game) if !games.contains(&game) => games.push(game),
+            GameMatch::Found(_) => {}
+            GameMatch::Ambiguous(candidates) => {
+                let picked = match pick_match(session, &resp, query, &candidates) {
+                    Ok(picked) => picked,
+                    Err(err) => return Ok(fail(args, &err.to_string(), FAILED)),
+                };
+                match picked {
+                    Some(Some(game)) => {
+                        if !games.contains(&game) {
+                            games.push(game);
+                        }
+                        continue;
+                    }
+                    Some(None) => return Ok(ExitCode::SUCCESS),
+                    None => {}
+                }
+
+                let mut message = format!("\"{query}\" could mean any of\n");
+                for candidate in candidates {
+                    message += &format!("  {}\n", output::clean(&candidate));
+                }
+                message += "please narrow it down with --game";
+                return Ok(fail(args, &message, FAILED));
+            }
+            GameMatch::None => {
+                let status = none_found(
+                    args,
+                    &format!(
+                        "no rooms are hosting \"{query}\", see --list-games for what's available"
+                    ),
+                    json_meta(args, listed, 0),
+                );
+                if args.count_summary {
+                    output::print_funnel(&[(listed, "listed"), (0, "hosting the game")]);
+                }
+                return Ok(status);
+            }
+        }
+    }
+
+    let rooms = resp
+        .rooms
+        .into_iter()
+        .filter(|room| games.contains(&room.game_name))
+        .collect::<Vec<_>>();
+    let hosting = rooms.len();
+
+    let mut rooms = rooms
+        .into_iter()
+        .filter(|room| room.players.len() >= args.min_players)
+        .filter(|room| !(args.hide_full && room.is_full()))
+        .filter(|room| !args.active || (room.players.len() >= 2 && !room.is_full()))
+        .filter(|room| !(args.no_password && room.has_password))
+        .filter(|room| !(args.strict_version && room.has_version_mismatch()))
+        .filter(|room| {
+            args.find_player
+                .as_ref()
+                .is_none_or(|player| room.has_player(player))
+        })
+        .filter(|room| args.room.as_ref().is_none_or(|name| room.name == *name))
+        .filter(|room| {
+            args.room_contains
+                .as_ref()
+                .is_none_or(|text| room.name.to_lowercase().contains(&text.to_lowercase()))
+        })
+        .filter(|room| {
+            args.description_contains.as_ref().is_none_or(|text| {
+                room.description.as_ref().is_some_and(|description| {
+                    description.to_lowercase().contains(&text.to_lowercase())
                 })
             })
         })
-        .min()
-        .map(Duration::from_millis)
+        .filter(|room| {
+            favorites
+                .as_ref()
+                .is_none_or(|favorites| favorites.contains(&room.address))
+        })
+        .collect::<Vec<_>>();
+    // the lists' networks are matched by what the addresses resolve to
+    let resolve_started = Instant::now();
+    skip_listed(session, &mut rooms).await;
+    let mut resolving = resolve_started.elapsed();
+    let mut pinging = Duration::ZERO;
+    let matched = rooms.len();
+    // only once the rooms were picked out by name, which would find nothing otherwise
+    if let Some(anonymizer) = &session.anonymizer {
+        anonymizer.apply(&mut rooms);
+    }
+    if let Some(labels) = &session.labels {
+        labels.apply(&mut rooms);
+    }
+
+    // looking for something in particular and not finding it deserves saying why
+    let missing = match (&args.find_player, &args.room, &args.room_contains) {
+        (Some(player), _, _) => Some(format!("{player} wasn't found in any room")),
+        (_, Some(name), _) => Some(format!("there's no room named \"{name}\"")),
+        (_, _, Some(text)) => Some(format!("no room's name contains \"{text}\"")),
+        _ => args
+            .description_contains
+            .as_ref()
+            .map(|text| format!("no room's description contains \"{text}\"")),
+    };
+    if let Some(missing) = missing {
+        if rooms.is_empty() {
+            if args.refresh_every().is_some() && args.is_interactive() && tty {
+                output::clear_screen();
+            }
+            let status = none_found(args, &missing, json_meta(args, listed, hosting));
+            if args.count_summary {
+                output::print_funnel(&[
+                    (listed, "listed"),
+                    (hosting, "hosting the game"),
+                    (0, "left after filtering"),
+                ]);
+            }
+            return Ok(status);
+        }
+    }
+
+    // https://no-color.org
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let colors = (tty && !no_color).then(|| Colors {
+        green_below: Duration::from_millis(args.green_below),
+        yellow_below: Duration::from_millis(args.yellow_below),
+    });
+
+    let json = JsonOptions {
+        fetched_at,
+        pinged: !args.no_ping,
+        pretty: args.json_pretty,
+        fields: args.fields.clone(),
+        meta: json_meta(args, listed, hosting),
+    };
+
+    let mut sampled = None;
+    if !args.no_ping {
+        let show_progress = args.is_interactive() && !args.no_progress;
+
+        // the rooms left out of the sample are left unpinged, like with --stop-after
+        let to_ping = match args.sample {
+            Some(n) if n < rooms.len() => {
+                sample(&mut rooms, n, args.seed);
+                sampled = Some(n);
+                n
+            }
+            _ => rooms.len(),
+        };
+        let rooms = &mut rooms[..to_ping];
+
+        // the bar is drawn on stderr, and not at all when that isn't a terminal
+        let bar = if show_progress && std::io::stderr().is_terminal() {
+            let bar = ProgressBar::new(rooms.len() as u64).with_style(progress_style(args));
+            // so that the spinner and the time keep going while waiting on slow rooms
+            bar.enable_steady_tick(Duration::from_millis(100));
+            bar
+        } else {
+            ProgressBar::hidden()
+        };
+
+        // done ahead of pinging to tell the two apart in --timings
+        let resolve_started = Instant::now();
+        tokio::select! {
+            () = yuzuping::resolve_rooms(rooms, &session.config) => {}
+            _ = cancel.cancelled() => {}
+        }
+        resolving += resolve_started.elapsed();
+        let ping_started = Instant::now();
+
+        // stopping early only cuts this refresh short, unlike ctrl-c
+        let stop = cancel.child_token();
+        let stop_below = args.stop_below.map(Duration::from_millis);
+        let mut good = 0;
+        let mut pinged = 0;
+        let mut streamed = 0;
+        // set for the second sweep of --timeout-escalation
+        let escalated = Cell::new(false);
+        if let Some(ipc) = &session.ipc {
+            ipc.refresh(&json);
+        }
+
+        let mut on_done = |room: &Room, done, total| {
+            bar.inc(1);
+            pinged = done;
+
+            let is_good = room
+                .ping
+                .stats()
+                .is_some_and(|ping| stop_below.is_none_or(|below| ping.min < below));
+            if is_good {
+                good += 1;
+                if args.stop_after.is_some_and(|n| good >= n) {
+                    stop.cancel();
+                }
+            }
+
+            // streamed as they come, so the filters that need the ping are applied
+            // here rather than afterwards
+            if args.format == Format::Ndjson || session.ipc.is_some() {
+                #[cfg(feature = "geoip")]
+                let country = session
+                    .geoip
+                    .as_ref()
+                    .and_then(|geoip| geoip.country(room.ip?));
+                #[cfg(not(feature = "geoip"))]
+                let country: Option<String> = None;
+
+                // the rooms that get another go are only printed after it
+                let last_go =
+                    room.ping.is_reachable() || session.escalation.is_none() || escalated.get();
+                let shown = args.limit.is_none_or(|limit| streamed < limit);
+                if last_go && shown && keep_pinged(args, room, country.as_deref()) {
+                    streamed += 1;
+                    if args.format == Format::Ndjson {
+                        output::print_json_line(room, country.as_deref(), &json);
+                    }
+                    if let Some(ipc) = &session.ipc {
+                        ipc.room(room, country.as_deref(), &json);
+                    }
+                }
+            }
+
+            if !(show_progress && args.live) {
+                return;
+            }
+
+            let min = room.ping.stats().map(|ping| ping.min);
+            let text = output::format_ping(&room.ping);
+            let text = match &colors {
+                Some(colors) => colors.paint(min, &text),
+                None => text,
+            };
+            let line = format!("{done}/{total}  {}  {text}", output::clean(&room.name));
+
+            // when piped, only the list itself should end up in the file
+            if !bar.is_hidden() {
+                bar.println(line);
+            } else if tty {
+                println!("{line}");
+            } else {
+                eprintln!("{line}");
+            }
+        };
+        let pinged_all =
+            ping_rooms(rooms, &session.config, &session.pinger, &stop, &mut on_done).await;
+        let pinged_all = match (pinged_all, &session.escalation) {
+            (Ok(()), Some((config, pinger))) if !stop.is_cancelled() => {
+                // the rooms that replied go first, the rest are pinged again with the
+                // longer timeout
+                rooms.sort_by_key(|room| !room.ping.is_reachable());
+                let replied = rooms.iter().filter(|room| room.ping.is_reachable()).count();
+                let again = &mut rooms[replied..];
+                bar.inc_length(again.len() as u64);
+                escalated.set(true);
+                ping_rooms(again, config, pinger, &stop, &mut on_done).await
+            }
+            (pinged_all, _) => pinged_all,
+        };
+        bar.finish_and_clear();
+        pinged_all?;
+        pinging = ping_started.elapsed();
+        if let Some(ipc) = &session.ipc {
+            let reachable = rooms.iter().filter(|room| room.ping.is_reachable()).count();
+            ipc.done(rooms.len(), reachable);
+        }
+
+        if stop.is_cancelled() && !cancel.is_cancelled() {
+            tracing::info!(
+                "stopped after {good} rooms replied, {} weren't pinged",
+                rooms.len() - pinged
+            );
+        }
+    }
+
+    if !args.no_merge {
+        rooms = merge_mirrored(rooms);
+    }
+    let replied = rooms.iter().filter(|room| room.ping.is_reachable()).count();
+
+    #[cfg(feature = "geoip")]
+    if let Some(geoip) = &session.geoip {
+        geoip.locate(&mut rooms);
+    }
+    rooms.retain(|room| keep_pinged(args, room, room.country.as_deref()));
+
+    if args.count_summary {
+        let mut funnel = vec![
+            (listed, "listed"),
+            (hosting, "hosting the game"),
+            (matched, "left after filtering"),
+        ];
+        if !args.no_ping {
+            let reachable = rooms.iter().filter(|room| room.ping.is_reachable()).count();
+            funnel.push((reachable, "reachable"));
+        }
+        output::print_funnel(&funnel);
+    }
+    if args.timings {
+        output::print_timings(&[
+            ("fetch", fetching),
+            ("resolve", resolving),
+            ("ping", pinging),
+            ("total", started.elapsed()),
+        ]);
+    }
+
+    // there's nothing to sort by ping without pings
+    let sort = match args.sort {
+        SortKey::Ping | SortKey::Score if args.no_ping => SortKey::Players,
+        sort => sort,
+    };
+    sort_rooms(
+        &mut rooms,
+        (sort, args.sort_secondary),
+        args.sort_stat,
+        args.player_weight,
+        args.reverse,
+        args.unreachable_first,
+        args.bots_last,
+    );
+    if args.group_by_game() {
+        // stable, so each game's rooms keep their order
+        rooms.sort_by_key(|room| games.iter().position(|game| *game == room.game_name));
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = &session.metrics {
+        metrics.update(&games, &rooms);
+    }
+
+    // a refresh cut short by ctrl-c is missing rooms, the next one would announce them
+    if let Some(webhook) = &session.webhook {
+        if !cancel.is_cancelled() {
+            webhook.notify(&session.webhook_client, &rooms).await;
+        }
+    }
+
+    if let Some(path) = &args.report {
+        if let Err(err) = output::write_report(path, &rooms, &json) {
+            tracing::warn!("unable to write the report to {}: {err}", path.display());
+        }
+    }
+
+    if args.check {
+        return Ok(check(args, &rooms));
+    }
+
+    if let Some(template) = &args.statusline {
+        output::print_statusline(template, &rooms, &games);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.best_only || args.print_address {
+        // the closest by the sort, which --sort and --reverse can make something else
+        let Some(best) = rooms.iter().find(|room| room.ping.is_reachable()) else {
+            let code = if matched == 0 {
+                NO_MATCHING_ROOMS
+            } else {
+                NONE_REACHABLE
+            };
+            return Ok(empty(args, code));
+        };
+        let ping = best
+            .ping
+            .stats()
+            .expect("only reachable rooms were looked for");
+        if args.print_address {
+            println!("{}:{}", best.address, best.port);
+        } else {
+            let latency = output::format_latency(ping.min);
+            println!("{latency} {}", output::clean(&best.name));
+        }
+        return Ok(too_few_replied(args, replied).unwrap_or(ExitCode::SUCCESS));
+    }
+
+    // every room is pinged regardless so that the ones shown really are the top
+    let shown = args.limit.unwrap_or(rooms.len()).min(rooms.len());
+    let (shown, hidden) = rooms.split_at(shown);
+
+    if args.refresh_every().is_some() && args.is_interactive() && tty {
+        output::clear_screen();
+    }
+
+    // the cached rooms have their real names, and this run's are placeholders
+    let changes = if args.no_cache || args.anonymize {
+        None
+    } else {
+        let changes = Cache::load().map(|cache| cache.compare(&games, &rooms, args.verbose));
+        if let Err(err) = Cache::new(&games, &rooms).save() {
+            tracing::warn!("unable to save the rooms for next time: {err}");
+        }
+        changes
+    };
+
+    let trends = session
+        .history
+        .as_ref()
+        .map(|history| history.record(&rooms));
+
+    // the text output only lists rooms that didn't reply when asked where to put them
+    let show_unreachable = args.show_unreachable || args.unreachable_first || args.unreachable_last;
+    let hidden = hidden
+        .iter()
+        .filter(|room| room.ping.is_reachable() || show_unreachable)
+        .count();
+    let options = PlainOptions {
+        // with several games in one list, tell which room is hosting what
+        show_game: games.len() > 1 && !args.group_by_game(),
+        group_by_game: args.group_by_game(),
+        show_players: args.show_players,
+        show_description: args.show_description,
+        show_host: args.resolve,
+        #[cfg(feature = "geoip")]
+        show_region: session.geoip.is_some(),
+        #[cfg(not(feature = "geoip"))]
+        show_region: false,
+        verbose: args.verbose,
+        sparkline: args.sparkline || args.verbose,
+        bars: args.bars,
+        trends,
+        colors,
+        changes,
+        offline,
+        fetched_at,
+        listed,
+        name_width: args.name_width,
+        pinged: !args.no_ping,
+        show_unreachable,
+        template: args.format_template.clone(),
+    };
+
+    if args.interactive {
+        return Ok(pick_room(args, shown));
+    }
+
+    match args.format {
+        Format::Table if options.template.is_none() => output::print_table(shown, hidden, &options),
+        Format::Table | Format::Plain => output::print_plain(shown, hidden, &options),
+        Format::Json => output::print_json(shown, &json),
+        // already printed while pinging, unless nothing was pinged
+        Format::Ndjson => {
+            if args.no_ping {
+                for room in shown {
+                    output::print_json_line(room, room.country.as_deref(), &json);
+                }
+            }
+        }
+        Format::Csv => {
+            if let Err(err) = output::print_csv(shown, !args.no_ping) {
+                tracing::warn!("unable to write csv: {err}");
+            }
+        }
+    }
+
+    if args.is_interactive() {
+        let fallback = args.fallback_probe.probe();
+        output::print_summary(&rooms, !args.no_ping, sampled, fallback);
+    }
+
+    if let Some(index) = args.launch {
+        // the same numbering as the table
+        let printed = options.printed(shown);
+        let Some(room) = index.checked_sub(1).and_then(|i| printed.clone().nth(i)) else {
+            eprintln!(
+                "there's no room {index} to launch, pick one from 1 to {}",
+                printed.count()
+            );
+            return Ok(ExitCode::FAILURE);
+        };
+
+        return Ok(launch(args, room));
+    }
+
+    if matched == 0 {
+        Ok(empty(args, NO_MATCHING_ROOMS))
+    } else if let Some(status) = too_few_replied(args, replied) {
+        Ok(status)
+    } else if !args.no_ping && rooms.iter().all(|room| !room.ping.is_reachable()) {
+        Ok(empty(args, NONE_REACHABLE))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+// the filters that can only be applied once `room` is pinged and located in `country`
+fn keep_pinged(args: &Args, room: &Room, country: Option<&str>) -> bool {
+    #[cfg(feature = "geoip")]
+    if let Some(region) = &args.region {
+        if !country.is_some_and(|country| country.eq_ignore_ascii_case(region)) {
+            return false;
+        }
+    }
+    #[cfg(not(feature = "geoip"))]
+    let _ = country;
+
+    if args.unreachable_hide && !room.ping.is_reachable() {
+        return false;
+    }
+
+    args.max_ping.is_none_or(|max| {
+        room.ping
+            .stats()
+            .is_some_and(|ping| ping.min <= Duration::from_millis(max))
+    })
+}
+
+// the ETA goes by how fast rooms have been done so far, which says nothing while waiting
+// on a few slow rooms. what it can't take longer than is every room timing out, a batch
+// of --concurrency at a time
+fn progress_style(args: &Args) -> ProgressStyle {
+    // the rooms pinged again by --timeout-escalation add to the length of the bar
+    let timeout = args
+        .timeout
+        .max(args.timeout_escalation.unwrap_or_default());
+    let timeout = Duration::from_millis(timeout);
+    let count = u32::from(args.count) + u32::from(args.warmup);
+    let per_room = match args.probe {
+        // the system ping waits about a second between requests
+        ProbeArg::Icmp | ProbeArg::Both => (Duration::from_secs(1) + timeout) * count,
+        _ => timeout * count,
+    };
+    // the tcp and udp probes take no longer than their timeouts
+    let per_room = match args.fallback_probe.probe() {
+        Some(_) => per_room + timeout * count,
+        None => per_room,
+    } * (args.ping_retries + 1);
+    let concurrency = args.concurrency.least() as u64;
+
+    ProgressStyle::with_template(
+        "{spinner} {bar:20} {pos}/{len} pinged, {per_sec:1} for {elapsed}, {eta} left, \
+         {most} at most",
+    )
+    .expect("the template is valid")
+    .with_key(
+        "most",
+        move |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+            let batches = state.len().unwrap_or(0).div_ceil(concurrency);
+            let most = (per_room * batches as u32).saturating_sub(state.elapsed());
+            let _ = write!(w, "{:#}", HumanDuration(most));
+        },
+    )
+}
+
+// serde's own error only tells which field it tripped over, which reads like a bug
+// rather than like the lobby having moved on
+fn lobby_error(args: &Args, err: &Error) -> String {
+    if !err.is_unfamiliar_schema() {
+        return err.to_string();
+    }
+
+    let message = "the lobby's schema looks unfamiliar, your yuzuping may be out of date or \
+                   the lobby may need another --schema";
+    if args.verbose {
+        format!("{message}\n{err}")
+    } else {
+        format!("{message} (see --verbose for why)")
+    }
+}
+
+/// Say why the run failed and exit with `code`. With --format json the reason goes to
+/// stdout as well, so that whatever reads the output is always handed JSON.
+fn fail(args: &Args, message: &str, code: u8) -> ExitCode {
+    if args.check {
+        let (level, code) = match code {
+            FETCH_FAILED => ("CRIT", CHECK_CRITICAL),
+            NO_MATCHING_ROOMS | NONE_REACHABLE => ("WARN", CHECK_WARNING),
+            _ => ("UNKNOWN", CHECK_UNKNOWN),
+        };
+        // the first line says what's wrong, the rest is detail for a person
+        let reason = message.lines().next().unwrap_or_default();
+        println!("{level}: {reason}");
+        return ExitCode::from(code);
+    }
+
+    eprintln!("{message}");
+    if matches!(args.format, Format::Json | Format::Ndjson) {
+        output::print_json_error(message, code);
+    }
+    ExitCode::from(code)
+}
+
+// for when the rooms run out before getting as far as printing them
+fn none_found(args: &Args, message: &str, meta: Option<JsonMeta>) -> ExitCode {
+    if args.format == Format::Csv && !args.check {
+        if let Err(err) = output::print_csv(&[], !args.no_ping) {
+            tracing::warn!("unable to write csv: {err}");
+        }
+    }
+    if !args.ignore_empty {
+        return fail(args, message, NO_MATCHING_ROOMS);
+    }
+
+    eprintln!("{message}");
+    if args.format == Format::Json {
+        output::print_json_empty(meta, args.json_pretty);
+    }
+    ExitCode::SUCCESS
+}
+
+fn json_meta(args: &Args, fetched: usize, matched_game: usize) -> Option<JsonMeta> {
+    (!args.flat_json).then_some(JsonMeta {
+        fetched,
+        matched_game,
+    })
+}
+
+// the rooms left are already under --max-ping, it only has to be said
+fn check(args: &Args, rooms: &[Room]) -> ExitCode {
+    let reachable = rooms.iter().filter(|room| room.ping.is_reachable()).count();
+    let which = match args.max_ping {
+        Some(max) => format!("under {max}ms"),
+        None => "reachable".to_string(),
+    };
+    if reachable == 0 {
+        return fail(args, &format!("no rooms {which}"), NONE_REACHABLE);
+    }
+
+    println!("OK: {reachable} rooms {which}");
+    ExitCode::SUCCESS
+}
+
+// --require-reachable is about the network as a whole, so it's only checked once
+// whatever did reply has been printed
+fn too_few_replied(args: &Args, replied: usize) -> Option<ExitCode> {
+    let required = args
+        .required_reachable()
+        .filter(|&required| replied < required)?;
+    eprintln!("only {replied} rooms replied, at least {required} had to");
+    Some(ExitCode::from(NONE_REACHABLE))
+}
+
+// nothing to show is only a failure when something was expected
+fn empty(args: &Args, code: u8) -> ExitCode {
+    if args.ignore_empty {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(code)
+    }
+}
+
+fn compare_lobbies(args: &Args, old: &Path, new: &Path) -> ExitCode {
+    // the same one for both, so that a room keeps its name from one to the other
+    let anonymizer = args.anonymize.then(Anonymizer::default);
+    let mut lobbies = Vec::new();
+    for path in [old, new] {
+        match read_lobby(path, args.schema.into()) {
+            Ok(mut resp) => {
+                resp.dedup();
+                if let Some(anonymizer) = &anonymizer {
+                    anonymizer.apply(&mut resp.rooms);
+                }
+                lobbies.push(resp);
+            }
+            Err(err) => {
+                let message = format!("unable to read the lobby from {}: {err}", path.display());
+                return fail(args, &message, FAILED);
+            }
+        }
+    }
+
+    let diff = diff::Diff::new(&lobbies[0], &lobbies[1]);
+    if args.format == Format::Json {
+        let json = if args.json_pretty {
+            serde_json::to_string_pretty(&diff)
+        } else {
+            serde_json::to_string(&diff)
+        };
+        println!("{}", json.expect("the diff is always serializable"));
+    } else {
+        diff.print();
+    }
+    ExitCode::SUCCESS
+}
+
+fn edit_favorites(args: &Args) -> ExitCode {
+    let mut favorites = match Favorites::load() {
+        Ok(favorites) => favorites,
+        Err(err) => {
+            eprintln!("unable to read the favorites: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for address in &args.add_favorite {
+        if favorites.add(address) {
+            println!("added {address} to the favorites");
+        } else {
+            println!("{address} already is a favorite");
+        }
+    }
+    for address in &args.remove_favorite {
+        if favorites.remove(address) {
+            println!("removed {address} from the favorites");
+        } else {
+            println!("{address} isn't a favorite");
+        }
+    }
+
+    match favorites.save() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("unable to save the favorites: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// someone who double-clicked the executable may not know that there's more to it than
+// smash, but scripts shouldn't be left waiting on a prompt
+fn ask_for_game(resp: &Response) -> dialoguer::Result<Option<String>> {
+    let games = resp.games();
+    let tty = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    if !tty || games.is_empty() {
+        return Ok(Some(DEFAULT_GAME.to_string()));
+    }
+
+    let default = games
+        .iter()
+        .position(|&(game, _)| game == DEFAULT_GAME)
+        .unwrap_or(0);
+    let picked = pick::pick_game("Which game? (pass --game to skip this)", &games, default)?;
+    Ok(picked.map(|index| games[index].0.to_string()))
+}
+
+/// Ask which of the `candidates` that `query` matches was meant, remembering the answer
+/// for the next refreshes. `None` when there's no terminal to ask in, and `Some(None)`
+/// if they backed out.
+fn pick_match(
+    session: &Session,
+    resp: &Response,
+    query: &str,
+    candidates: &[String],
+) -> dialoguer::Result<Option<Option<String>>> {
+    let mut picked = session
+        .picked_matches
+        .lock()
+        .expect("nothing panics while holding the lock");
+    // a game that stopped being hosted is asked about again
+    if let Some(game) = picked.get(query).filter(|game| candidates.contains(game)) {
+        return Ok(Some(Some(game.clone())));
+    }
+
+    let tty = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    if !tty {
+        return Ok(None);
+    }
+
+    // most hosted first, like the candidates
+    let games = resp
+        .games()
+        .into_iter()
+        .filter(|(game, _)| candidates.iter().any(|candidate| candidate == game))
+        .collect::<Vec<_>>();
+    let prompt = format!("\"{query}\" could mean any of these, which one?");
+    let Some(index) = pick::pick_game(&prompt, &games, 0)? else {
+        return Ok(Some(None));
+    };
+
+    let game = games[index].0.to_string();
+    picked.insert(query.to_string(), game.clone());
+    Ok(Some(Some(game)))
+}
+
+fn pick_room(args: &Args, rooms: &[Room]) -> ExitCode {
+    if !std::io::stdout().is_terminal() {
+        eprintln!("--interactive needs a terminal to pick from");
+        return ExitCode::FAILURE;
+    }
+
+    match pick::pick(rooms, args.launch_cmd.is_some()) {
+        Ok(Some((room, Action::Launch))) => launch(args, room),
+        Ok(Some((room, Action::Copy))) => {
+            let address = format!("{}:{}", room.address, room.port);
+            match pick::copy(&address) {
+                Ok(()) => println!("copied {address}"),
+                Err(err) => println!("unable to copy {address}: {err}"),
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(None) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn launch(args: &Args, room: &Room) -> ExitCode {
+    let Some(template) = &args.launch_cmd else {
+        eprintln!("set --launch-cmd or YUZU_LAUNCH_CMD to the command that joins a room");
+        return ExitCode::FAILURE;
+    };
+
+    let mut words = template.split_whitespace().map(|word| {
+        word.replace("{address}", &room.address)
+            .replace("{port}", &room.port.to_string())
+    });
+    let Some(program) = words.next() else {
+        eprintln!("the --launch-cmd is empty");
+        return ExitCode::FAILURE;
+    };
+
+    // the emulator is left running on its own
+    match std::process::Command::new(&program).args(words).spawn() {
+        Ok(_) => {
+            println!("joining {}", output::clean(&room.name));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("unable to run {program}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// the lobby only changes so often, and running again straight away shouldn't ask for
+// it again
+fn cached_lobby(args: &Args) -> Option<LobbyCache> {
+    if args.no_cache {
+        return None;
+    }
+    let cached = LobbyCache::load(&args.url, Duration::from_secs(args.cache_ttl))?;
+    tracing::debug!("using the lobby fetched less than {}s ago", args.cache_ttl);
+    Some(cached)
+}
+
+// lobbies are fetched at the same time. the ones that can't be reached are skipped as
+// long as at least one can
+async fn fetch_all(args: &Args, client: &reqwest::Client) -> Result<Response, Error> {
+    let retries = if args.fail_fast { 0 } else { args.retries };
+    let results = futures::future::join_all(
+        args.url
+            .iter()
+            .map(|url| fetch_with_retries(client, url, args.schema.into(), retries)),
+    )
+    .await;
+
+    let mut responses = Vec::new();
+    let mut failures = Vec::new();
+    for (url, result) in args.url.iter().zip(results) {
+        match result {
+            Ok(resp) => {
+                if args.verbose {
+                    tracing::info!("{url}: {} rooms", resp.rooms.len());
+                } else {
+                    tracing::debug!("{url}: {} rooms", resp.rooms.len());
+                }
+                responses.push(resp);
+            }
+            Err(err) => failures.push((url, err)),
+        }
+    }
+
+    if responses.is_empty() || (args.fail_fast && !failures.is_empty()) {
+        let (_, err) = failures
+            .into_iter()
+            .next()
+            .expect("there's always at least one url");
+        return Err(err);
+    }
+    for (url, err) in failures {
+        tracing::warn!("skipping {url}: {err}");
+    }
+
+    Ok(Response::merge(responses))
+}
+
+// only connection problems are worth retrying, a lobby that answered with garbage
+// will most likely do so again
+async fn fetch_with_retries(
+    client: &reqwest::Client,
+    url: &str,
+    schema: LobbySchema,
+    retries: u32,
+) -> Result<Response, Error> {
+    let mut backoff = Duration::from_millis(500);
+    let mut attempt = 0;
+
+    loop {
+        match fetch_lobby(client, url, schema).await {
+            Err(Error::Http(err))
+                if attempt < retries && (err.is_connect() || err.is_timeout()) =>
+            {
+                attempt += 1;
+                tracing::warn!(
+                    "couldn't reach the yuzu lobby, retrying in {backoff:?} ({attempt}/{retries})"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+// unreachable rooms are kept together at one end no matter the key or the order. Among
+// them there's no ping to sort by, so the most populated come first instead
+fn sort_rooms(
+    rooms: &mut [Room],
+    (key, secondary): (SortKey, SortKey),
+    stat: Stat,
+    player_weight: f64,
+    reverse: bool,
+    unreachable_first: bool,
+    bots_last: bool,
+) {
+    let score = |room: &Room, ping: PingStats| {
+        stat.of(&ping).as_secs_f64() * 1000.0 - player_weight * room.players.len() as f64
+    };
+    let by = |key, left: &Room, right: &Room| match (key, left.ping.stats(), right.ping.stats()) {
+        (SortKey::Ping, Some(left), Some(right)) => stat.of(&left).cmp(&stat.of(&right)),
+        (SortKey::Score, Some(left_ping), Some(right_ping)) => {
+            score(left, left_ping).total_cmp(&score(right, right_ping))
+        }
+        (SortKey::Ping | SortKey::Score, _, _) => Ordering::Equal,
+        (SortKey::Players, _, _) => right.players.len().cmp(&left.players.len()),
+        (SortKey::Name, _, _) => left.name.cmp(&right.name),
+    };
+    // ties go by the secondary key and then the name, rather than by where the lobby
+    // happened to list the rooms this time
+    let tied = |left: &Room, right: &Room| {
+        by(secondary, left, right).then_with(|| by(SortKey::Name, left, right))
+    };
+
+    // where the unreachable rooms go is up to their own flags rather than --reverse
+    rooms.sort_by(|left, right| {
+        let unreachable = right.ping.is_reachable().cmp(&left.ping.is_reachable());
+        if unreachable != Ordering::Equal {
+            return if unreachable_first {
+                unreachable.reverse()
+            } else {
+                unreachable
+            };
+        }
+
+        // regardless of --reverse, which is about the sort
+        let empty = left.players.is_empty().cmp(&right.players.is_empty());
+        if bots_last && empty != Ordering::Equal {
+            return empty;
+        }
+
+        // the unreachable rooms left, there's no ping to rank them by
+        if !left.ping.is_reachable() && matches!(key, SortKey::Ping | SortKey::Score) {
+            return by(SortKey::Players, left, right).then_with(|| tied(left, right));
+        }
+
+        let order = by(key, left, right).then_with(|| tied(left, right));
+        if reverse {
+            order.reverse()
+        } else {
+            order
+        }
+    });
 }
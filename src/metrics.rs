@@ -0,0 +1,144 @@
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use yuzuping::Room;
+
+/// The gauges of the latest refresh, served in the prometheus text format at `/metrics`.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    text: Arc<Mutex<String>>,
+}
+
+impl Metrics {
+    /// Start serving on `addr` in the background.
+    pub async fn serve(addr: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let metrics = Metrics {
+            text: Arc::default(),
+        };
+
+        tokio::spawn({
+            let metrics = metrics.clone();
+            async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(metrics.clone().respond(stream));
+                        }
+                        Err(err) => tracing::debug!("unable to accept a scrape: {err}"),
+                    }
+                }
+            }
+        });
+
+        Ok(metrics)
+    }
+
+    /// Replace the gauges with those of `rooms`, the ones left after filtering.
+    pub fn update(&self, games: &[String], rooms: &[Room]) {
+        let reachable = rooms.iter().filter(|room| room.ping.is_reachable()).count();
+        let players = rooms.iter().map(|room| room.players.len()).sum::<usize>();
+
+        let mut text = String::new();
+        gauge(
+            &mut text,
+            "yuzuping_rooms_total",
+            "Rooms listed",
+            rooms.len(),
+        );
+        gauge(
+            &mut text,
+            "yuzuping_rooms_reachable",
+            "Rooms that replied to the pings",
+            reachable,
+        );
+        gauge(
+            &mut text,
+            "yuzuping_players_total",
+            "Players in the listed rooms",
+            players,
+        );
+
+        // games without a reachable room are left out rather than reported as 0ms
+        text.push_str("# HELP yuzuping_best_ping_ms Lowest ping to a room of the game\n");
+        text.push_str("# TYPE yuzuping_best_ping_ms gauge\n");
+        for game in games {
+            let best = rooms
+                .iter()
+                .filter(|room| &room.game_name == game)
+                .filter_map(|room| room.ping.stats().map(|ping| ping.min))
+                .min();
+            if let Some(best) = best {
+                let _ = writeln!(
+                    text,
+                    "yuzuping_best_ping_ms{{game=\"{}\"}} {}",
+                    escape(game),
+                    best.as_secs_f64() * 1000.0
+                );
+            }
+        }
+
+        *self
+            .text
+            .lock()
+            .expect("nothing panics while holding the lock") = text;
+    }
+
+    async fn respond(self, mut stream: TcpStream) {
+        // only the request line matters, the rest of the request is read and thrown away
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        let read = tokio::time::timeout(Duration::from_secs(5), async {
+            let done = |request: &[u8]| request.windows(4).any(|window| window == b"\r\n\r\n");
+            while !done(&request) && request.len() < 8192 {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+        })
+        .await;
+        if read.is_err() {
+            return;
+        }
+
+        let path = request.split(|&b| b == b' ').nth(1).unwrap_or_default();
+        let (status, body) = if path == b"/metrics" {
+            let text = self
+                .text
+                .lock()
+                .expect("nothing panics while holding the lock");
+            ("200 OK", text.clone())
+        } else {
+            ("404 Not Found", "see /metrics\n".to_string())
+        };
+
+        let response = format!(
+            "HTTP/1.1 {status}\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        );
+        if let Err(err) = stream.write_all(response.as_bytes()).await {
+            tracing::debug!("unable to answer a scrape: {err}");
+        }
+    }
+}
+
+fn gauge(text: &mut String, name: &str, help: &str, value: usize) {
+    let _ = write!(
+        text,
+        "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n"
+    );
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
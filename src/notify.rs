@@ -0,0 +1,100 @@
+use crate::output::{format_ping, occupancy};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use yuzuping::Room;
+
+// a room that drops out of the list for a refresh or two, say because a ping got lost,
+// isn't announced again when it comes back
+const FORGET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+// discord rejects messages longer than 2000 characters
+const MAX_ROOMS: usize = 10;
+
+/// Posts the rooms that showed up since the previous refresh to a discord webhook.
+#[derive(Debug)]
+pub struct Webhook {
+    url: String,
+    /// When each room was last in the list, keyed by address and port. `None` until the
+    /// first refresh, whose rooms are taken as already known.
+    seen: Mutex<Option<HashMap<(String, u32), Instant>>>,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> Self {
+        Webhook {
+            url,
+            seen: Mutex::new(None),
+        }
+    }
+
+    /// Announce the rooms that weren't in the list lately.
+    pub async fn notify(&self, client: &reqwest::Client, rooms: &[Room]) {
+        let new = self.new_rooms(rooms);
+        if new.is_empty() {
+            return;
+        }
+
+        let mut lines = new
+            .iter()
+            .take(MAX_ROOMS)
+            .map(|room| describe(room))
+            .collect::<Vec<_>>();
+        if new.len() > MAX_ROOMS {
+            lines.push(format!("and {} more", new.len() - MAX_ROOMS));
+        }
+        // room names are picked by whoever hosts them, so they don't get to @everyone
+        let body = serde_json::json!({
+            "content": lines.join("\n"),
+            "allowed_mentions": { "parse": [] },
+        });
+
+        let result = client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        match result {
+            Ok(_) => tracing::debug!("announced {} rooms", new.len()),
+            Err(err) => tracing::warn!("unable to post to the webhook: {err}"),
+        }
+    }
+
+    fn new_rooms<'a>(&self, rooms: &'a [Room]) -> Vec<&'a Room> {
+        let now = Instant::now();
+        let mut seen = self
+            .seen
+            .lock()
+            .expect("nothing panics while holding the lock");
+
+        let Some(seen) = seen.as_mut() else {
+            let known = rooms
+                .iter()
+                .map(|room| ((room.address.clone(), room.port), now))
+                .collect();
+            *seen = Some(known);
+            return Vec::new();
+        };
+
+        seen.retain(|_, last| now.duration_since(*last) < FORGET_AFTER);
+        rooms
+            .iter()
+            .filter(|room| {
+                seen.insert((room.address.clone(), room.port), now)
+                    .is_none()
+            })
+            .collect()
+    }
+}
+
+fn describe(room: &Room) -> String {
+    let players = occupancy(room);
+    let ping = format_ping(&room.ping);
+    let locked = if room.has_password { " [locked]" } else { "" };
+
+    format!(
+        "**{}**{locked} is up for {}: {players} players, {ping}",
+        room.name, room.game_name
+    )
+}
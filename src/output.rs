@@ -0,0 +1,1145 @@
+use crate::cache::Changes;
+use crate::history::Trends;
+use crate::template::{Status, StatusField, Template};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use yuzuping::{PingStats, Probe, ProbeResult, Room};
+
+/// Clear the terminal and move the cursor to the top left.
+pub fn clear_screen() {
+    print!("\x1b[2J\x1b[H");
+}
+
+/// Thresholds for coloring pings green, yellow and red.
+pub struct Colors {
+    pub green_below: Duration,
+    pub yellow_below: Duration,
+}
+
+impl Colors {
+    /// Wrap `text` in the color for `ping`, or dim it for unreachable rooms.
+    pub fn paint(&self, ping: Option<Duration>, text: &str) -> String {
+        let code = match ping {
+            Some(ping) if ping < self.green_below => "32",
+            Some(ping) if ping < self.yellow_below => "33",
+            Some(_) => "31",
+            None => "2",
+        };
+        format!("\x1b[{code}m{text}\x1b[0m")
+    }
+
+    /// Dim `text`, for the names of rooms nobody's playing in, which tend to be bots or
+    /// placeholders.
+    pub fn dim(&self, text: &str) -> String {
+        format!("\x1b[2m{text}\x1b[0m")
+    }
+
+    /// What each color means, like `<60ms <120ms ≥120ms` in green, yellow and red.
+    pub fn legend(&self) -> String {
+        let green = format!("<{}ms", self.green_below.as_millis());
+        let yellow = format!("<{}ms", self.yellow_below.as_millis());
+        let red = format!("≥{}ms", self.yellow_below.as_millis());
+        format!(
+            "{} {} {}",
+            self.paint(Some(Duration::ZERO), &green),
+            self.paint(Some(self.green_below), &yellow),
+            self.paint(Some(self.yellow_below), &red)
+        )
+    }
+}
+
+/// What to include in the human readable output beyond the room name and ping.
+pub struct PlainOptions {
+    /// Prefix rooms with the game they're hosting
+    pub show_game: bool,
+    /// Print a heading before the rooms of each game, which are next to each other
+    pub group_by_game: bool,
+    /// List the players under each room
+    pub show_players: bool,
+    /// Print the description of each room under it, above its players
+    pub show_description: bool,
+    /// Show the reverse DNS name of each room
+    pub show_host: bool,
+    /// Show the country of each room
+    pub show_region: bool,
+    /// Show every ping statistic rather than only the minimum
+    pub verbose: bool,
+    /// Show the latency of each reply as a sparkline
+    pub sparkline: bool,
+    /// Show how full each room is as a bar next to its players
+    pub bars: bool,
+    /// Color the pings, unless printing somewhere that doesn't understand colors
+    pub colors: Option<Colors>,
+    /// Point out what changed since the last run
+    pub changes: Option<Changes>,
+    /// How each room's ping moved over the latest refreshes while watching
+    pub trends: Option<Trends>,
+    /// The favorites that aren't in the lobby
+    pub offline: Vec<String>,
+    /// When the lobby was fetched
+    pub fetched_at: SystemTime,
+    /// How many rooms the lobby listed, before filtering
+    pub listed: usize,
+    /// Cut room names longer than this many columns short
+    pub name_width: Option<usize>,
+    /// Whether the rooms were pinged at all. If not, every room is printed without a
+    /// ping rather than left out as unreachable
+    pub pinged: bool,
+    /// List the rooms that didn't reply too, rather than leave them out
+    pub show_unreachable: bool,
+    /// Print each room like this instead of the usual line
+    pub template: Option<Template>,
+}
+
+impl PlainOptions {
+    /// The rooms of `rooms` that get printed, in the order the table numbers them.
+    pub fn printed<'a>(&self, rooms: &'a [Room]) -> impl Iterator<Item = &'a Room> + Clone {
+        let every = !self.pinged || self.show_unreachable;
+        rooms
+            .iter()
+            .filter(move |room| every || room.ping.is_reachable())
+    }
+}
+
+/// The label and markers to put after the name of the room, like ` (Alice's PC) [locked]`.
+pub fn tags(room: &Room) -> String {
+    let mut tags = String::new();
+    if let Some(label) = &room.label {
+        tags.push_str(&format!(" ({})", clean(label)));
+    }
+    if room.has_password {
+        tags.push_str(" [locked]");
+    }
+    if room.has_version_mismatch() {
+        tags.push_str(" [mismatch]");
+    }
+    tags
+}
+
+/// The name of `room`, safe to print and cut short to `--name-width`.
+pub fn name(room: &Room, options: &PlainOptions) -> String {
+    let name = clean(&room.name);
+    match options.name_width {
+        Some(width) => fit(&name, width),
+        None => name.into_owned(),
+    }
+}
+
+/// `time` in UTC, to the second, like `2024-05-01T12:00:03Z`.
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // the days since the epoch to a date, from howard hinnant's `civil_from_days`
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// The reverse DNS name of the room, or its IP when it doesn't have one.
+pub fn host(room: &Room) -> String {
+    match (&room.hostname, room.ip) {
+        (Some(hostname), _) => hostname.clone(),
+        (None, Some(ip)) => ip.to_string(),
+        (None, None) => room.address.clone(),
+    }
+}
+
+fn print_notes(options: &PlainOptions) {
+    println!(
+        "lobby fetched at {} ({} rooms)",
+        timestamp(options.fetched_at),
+        options.listed
+    );
+    if let Some(changes) = &options.changes {
+        if !changes.gone.is_empty() {
+            let gone = changes.gone.iter().map(|name| clean(name));
+            println!("gone: {}", gone.collect::<Vec<_>>().join(", "));
+        }
+    }
+    if !options.offline.is_empty() {
+        println!("offline: {}", options.offline.join(", "));
+    }
+    if let Some(colors) = options.colors.as_ref().filter(|_| options.pinged) {
+        println!("ping: {}", colors.legend());
+    }
+}
+
+/// Print the reachable rooms furthest first, so that the closest ones end up right above
+/// the prompt. `hidden` is how many more rooms were left out by `--limit`.
+pub fn print_plain(rooms: &[Room], hidden: usize, options: &PlainOptions) {
+    print_notes(options);
+    if hidden > 0 {
+        println!("…and {hidden} more");
+    }
+
+    let mut group = None;
+    for room in rooms.iter().rev() {
+        let ping = room.ping.stats();
+        if options.pinged && ping.is_none() && !options.show_unreachable {
+            continue;
+        }
+        if options.group_by_game {
+            print_group(&mut group, room);
+        }
+
+        let line = match &options.template {
+            Some(template) => template.render(room, options),
+            None => default_line(room, ping, options),
+        };
+        println!("{line}");
+
+        if options.show_description {
+            print_description(room);
+        }
+        if options.show_players {
+            print_players(room);
+        }
+    }
+}
+
+fn default_line(room: &Room, ping: Option<PingStats>, options: &PlainOptions) -> String {
+    let loss = room
+        .packet_loss
+        .map(|loss| format!(" {} loss", format_loss(loss)))
+        .unwrap_or_default();
+
+    let game = if options.show_game {
+        format!("[{}] ", clean(&room.game_name))
+    } else {
+        String::new()
+    };
+
+    let tags = tags(room);
+
+    let text = ping.map_or_else(
+        || {
+            if options.pinged {
+                format!("  {}", format_ping(&room.ping))
+            } else {
+                String::new()
+            }
+        },
+        |ping| {
+            let text = if options.verbose {
+                let via = room
+                    .probe
+                    .map(|probe| format!(" via {probe}"))
+                    .unwrap_or_default();
+                let tried = match room.other_ips.len() {
+                    0 => String::new(),
+                    others => format!(", the quickest of {} addresses", others + 1),
+                };
+                format!(
+                    "min {} avg {} max {} jitter {} p50 {} p95 {}{via}{tried}",
+                    format_latency(ping.min),
+                    format_latency(ping.avg),
+                    format_latency(ping.max),
+                    format_latency(ping.jitter),
+                    format_latency(ping.p50),
+                    format_latency(ping.p95)
+                )
+            } else {
+                format_latency(ping.min)
+            };
+            match &options.colors {
+                Some(colors) => format!("  {}", colors.paint(Some(ping.min), &text)),
+                None => format!("  {text}"),
+            }
+        },
+    );
+
+    let change = options
+        .changes
+        .as_ref()
+        .and_then(|changes| changes.of(room))
+        .map(|change| format!("  {change}"))
+        .unwrap_or_default();
+    let trend = options
+        .trends
+        .as_ref()
+        .and_then(|trends| trends.of(room))
+        .map(|trend| format!("  {trend}"))
+        .unwrap_or_default();
+
+    let host = if options.show_host {
+        format!(" ({})", host(room))
+    } else {
+        String::new()
+    };
+
+    let spark = match sparkline(&room.replies) {
+        spark if options.sparkline && !spark.is_empty() => format!("  {spark}"),
+        _ => String::new(),
+    };
+
+    let region = match &room.country {
+        Some(country) if options.show_region => format!(" [{country}]"),
+        _ => String::new(),
+    };
+
+    let bar = match occupancy_bar(room) {
+        Some(bar) if options.bars => format!(" {bar}"),
+        _ => String::new(),
+    };
+
+    let name = match &options.colors {
+        Some(colors) if room.players.is_empty() => colors.dim(&name(room, options)),
+        _ => name(room, options),
+    };
+
+    format!(
+        "{}{}{}{}{} ({} playing){}{}{}{}{}{}",
+        game,
+        name,
+        host,
+        region,
+        tags,
+        occupancy(room),
+        bar,
+        text,
+        loss,
+        spark,
+        trend,
+        change
+    )
+}
+
+/// How many players are in `room` out of how many it takes, like `3/8`, or only how many
+/// there are when the lobby doesn't say.
+pub fn occupancy(room: &Room) -> String {
+    match room.max_players {
+        Some(max) => format!("{}/{max}", room.players.len()),
+        None => room.players.len().to_string(),
+    }
+}
+
+/// How full `room` is as a bar like `[███░░░░░]`, when the lobby says how many it takes.
+fn occupancy_bar(room: &Room) -> Option<String> {
+    // a cell per player, unless that'd be a very long bar
+    const MAX_WIDTH: usize = 16;
+
+    let max = room.max_players.filter(|&max| max > 0)? as usize;
+    let width = max.min(MAX_WIDTH);
+    let filled = (room.players.len().min(max) * width + max / 2) / max;
+    Some(format!(
+        "[{}{}]",
+        "█".repeat(filled),
+        "░".repeat(width - filled)
+    ))
+}
+
+/// The latency of each of `replies` as a bar from the fastest to the slowest, like
+/// `▁▂▁█▂`, or nothing when there are fewer than two to compare.
+pub fn sparkline(replies: &[Duration]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let (Some(min), Some(max)) = (replies.iter().min(), replies.iter().max()) else {
+        return String::new();
+    };
+    if replies.len() < 2 {
+        return String::new();
+    }
+
+    let range = (*max - *min).as_secs_f64();
+    replies
+        .iter()
+        .map(|reply| {
+            // all of the same latency is as steady as it gets
+            let level = if range == 0.0 {
+                0
+            } else {
+                ((*reply - *min).as_secs_f64() / range * 7.0).round() as usize
+            };
+            BARS[level]
+        })
+        .collect()
+}
+
+/// Sum up the latest `probes` to `address`, where a lost probe is `None`. On a terminal
+/// the line is redrawn in place, otherwise every update gets a line of its own.
+pub fn print_monitor(address: &str, probes: &VecDeque<Option<Duration>>, tty: bool) {
+    let replies = probes.iter().flatten().copied().collect::<Vec<_>>();
+    let lost = probes.len() - replies.len();
+    let loss = lost as f32 / probes.len().max(1) as f32 * 100.0;
+
+    let stats = match PingStats::from_replies(&replies) {
+        Some(ping) => format!(
+            "min {} avg {} max {} jitter {}",
+            format_latency(ping.min),
+            format_latency(ping.avg),
+            format_latency(ping.max),
+            format_latency(ping.jitter)
+        ),
+        None => "no replies".to_string(),
+    };
+    let line = format!(
+        "{}: {stats}, {loss:.0}% loss over the last {} probes",
+        clean(address),
+        probes.len()
+    );
+
+    if tty {
+        let mut stdout = std::io::stdout().lock();
+        let _ = write!(stdout, "\r\x1b[2K{line}").and_then(|()| stdout.flush());
+    } else {
+        println!("{line}");
+    }
+}
+
+/// Print how many rooms were left after each step on stderr, like
+/// `120 listed → 14 hosting the game → 0 left after filtering`.
+pub fn print_funnel(steps: &[(usize, &str)]) {
+    let steps = steps
+        .iter()
+        .map(|(rooms, step)| format!("{rooms} {step}"))
+        .collect::<Vec<_>>();
+    eprintln!("{}", steps.join(" → "));
+}
+
+/// A latency in milliseconds with one decimal, like `12.3 ms` or `0.8 ms`, so that a
+/// column of them lines up whether the rooms are across the world or on the LAN.
+pub fn format_latency(latency: Duration) -> String {
+    format!("{:.1} ms", millis(latency))
+}
+
+/// A packet loss rounded to a whole percentage, like `33%` for one probe lost out of
+/// three.
+pub fn format_loss(loss: f32) -> String {
+    format!("{loss:.0}%")
+}
+
+/// A latency in fractional milliseconds for the machine readable outputs, which keep
+/// the sub-millisecond pings of LAN rooms apart rather than rounding them to 0.
+pub fn millis(latency: Duration) -> f64 {
+    latency.as_secs_f64() * 1000.0
+}
+
+/// The minimum ping of a room that replied, or what became of the probes otherwise,
+/// like `timed out`.
+pub fn format_ping(result: &ProbeResult) -> String {
+    match result {
+        ProbeResult::Reachable(stats) => format_latency(stats.min),
+        ProbeResult::Pending => "not pinged".to_string(),
+        ProbeResult::Timeout => "timed out".to_string(),
+        ProbeResult::Unreachable => "unreachable".to_string(),
+        ProbeResult::ResolveFailed => "unresolved".to_string(),
+        ProbeResult::Error(_) => "failed".to_string(),
+    }
+}
+
+/// Print how long each phase took on stderr, like
+/// `fetch 212.4ms, resolve 3.1ms, ping 2.3s, total 2.5s`.
+pub fn print_timings(phases: &[(&str, Duration)]) {
+    let phases = phases
+        .iter()
+        .map(|(phase, took)| format!("{phase} {took:.1?}"))
+        .collect::<Vec<_>>();
+    eprintln!("{}", phases.join(", "));
+}
+
+/// Print a line summing up every room that passed the filters, including those left out
+/// by `--limit`. `sampled` is how many of them were pinged with `--sample`, and how many
+/// only replied to the `fallback` probe is told too.
+pub fn print_summary(
+    rooms: &[Room],
+    pinged: bool,
+    sampled: Option<usize>,
+    fallback: Option<Probe>,
+) {
+    let totals = Totals::of(rooms);
+
+    if !pinged {
+        println!("{} rooms, {} players", totals.rooms, totals.players);
+        return;
+    }
+
+    let mut summary = format!("{} rooms, ", totals.rooms);
+    if let Some(sampled) = sampled {
+        summary += &format!("a sample of {sampled} pinged, ");
+    }
+    summary += &format!("{} reachable", totals.reachable);
+    if let Some(fallback) = fallback {
+        let by_fallback = rooms
+            .iter()
+            .filter(|room| room.ping.is_reachable() && room.probe == Some(fallback))
+            .count();
+        summary += &format!(" ({by_fallback} by {fallback})");
+    }
+    summary += &format!(", {} players", totals.players);
+    if let (Some(median), Some((best, ping))) = (totals.median, totals.best) {
+        summary += &format!(
+            ", median {}, best '{}' {}",
+            format_latency(median),
+            clean(&best.name),
+            format_latency(ping)
+        );
+    }
+
+    println!("{summary}");
+}
+
+/// Print the rooms summed up on a single line with `template`, for --statusline.
+pub fn print_statusline(template: &Template<StatusField>, rooms: &[Room], games: &[String]) {
+    let totals = Totals::of(rooms);
+    println!(
+        "{}",
+        template.render_status(&Status {
+            games,
+            rooms: totals.rooms,
+            reachable: totals.reachable,
+            players: totals.players,
+            best: totals.best,
+        })
+    );
+}
+
+/// What [`print_summary`] sums up, by the minimum ping of each room.
+struct Totals<'a> {
+    rooms: usize,
+    reachable: usize,
+    players: usize,
+    median: Option<Duration>,
+    best: Option<(&'a Room, Duration)>,
+}
+
+impl<'a> Totals<'a> {
+    fn of(rooms: &'a [Room]) -> Self {
+        let mut reachable = rooms
+            .iter()
+            .filter_map(|room| Some((room, room.ping.stats()?.min)))
+            .collect::<Vec<_>>();
+        reachable.sort_by_key(|&(_, ping)| ping);
+
+        let middle = reachable.len() / 2;
+        let median = match reachable.len() {
+            0 => None,
+            n if n % 2 == 0 => Some((reachable[middle - 1].1 + reachable[middle].1) / 2),
+            _ => Some(reachable[middle].1),
+        };
+
+        Totals {
+            rooms: rooms.len(),
+            reachable: reachable.len(),
+            players: rooms.iter().map(|room| room.players.len()).sum(),
+            median,
+            best: reachable.first().copied(),
+        }
+    }
+}
+
+/// Room names and games longer than this many columns are cut short in the table.
+const MAX_CELL_WIDTH: usize = 32;
+
+/// Like [`print_plain`], but with every column lined up under a header. Verbose tables
+/// get a column for each ping statistic.
+pub fn print_table(rooms: &[Room], hidden: usize, options: &PlainOptions) {
+    let mut header = vec!["#", "Name"];
+    if options.show_host {
+        header.push("Host");
+    }
+    if options.show_region {
+        header.push("Region");
+    }
+    header.extend(["Game", "Players"]);
+    match (options.pinged, options.verbose) {
+        (false, _) => {}
+        (true, true) => header.extend(["Min", "Avg", "Max", "Jitter", "P50", "P95", "Loss"]),
+        (true, false) => header.extend(["Ping", "Loss"]),
+    }
+    let show_sparkline = options.pinged && options.sparkline;
+    if show_sparkline {
+        header.push("Replies");
+    }
+    // only when pinging with more than one probe
+    let show_probe = options.verbose && rooms.iter().any(|room| room.probe.is_some());
+    if show_probe {
+        header.push("Via");
+    }
+    if options.trends.is_some() {
+        header.push("Trend");
+    }
+    if options.changes.is_some() {
+        header.push("Change");
+    }
+
+    // the numbers line up on their last digit
+    let right_aligned = header.iter().map(|&column| {
+        !matches!(
+            column,
+            "Name" | "Host" | "Region" | "Game" | "Replies" | "Via" | "Trend" | "Change"
+        )
+    });
+    let right_aligned = right_aligned.collect::<Vec<_>>();
+
+    // numbered closest first, counting only the rooms that are printed
+    let rows = options
+        .printed(rooms)
+        .enumerate()
+        .map(|(index, room)| {
+            let tags = tags(room);
+            let players = match occupancy_bar(room) {
+                Some(bar) if options.bars => format!("{} {bar}", occupancy(room)),
+                _ => occupancy(room),
+            };
+
+            let mut cells = vec![
+                (index + 1).to_string(),
+                fit(
+                    &format!("{}{tags}", clean(&room.name)),
+                    options.name_width.unwrap_or(MAX_CELL_WIDTH),
+                ),
+            ];
+            if options.show_host {
+                cells.push(fit(&host(room), MAX_CELL_WIDTH));
+            }
+            if options.show_region {
+                cells.push(room.country.clone().unwrap_or_default());
+            }
+            cells.extend([fit(&clean(&room.game_name), MAX_CELL_WIDTH), players]);
+            if let Some(ping) = room.ping.stats() {
+                if options.verbose {
+                    let stats = [
+                        ping.min,
+                        ping.avg,
+                        ping.max,
+                        ping.jitter,
+                        ping.p50,
+                        ping.p95,
+                    ];
+                    cells.extend(stats.map(format_latency));
+                } else {
+                    cells.push(format_latency(ping.min));
+                }
+                cells.push(room.packet_loss.map(format_loss).unwrap_or_default());
+            } else if options.pinged {
+                let stats = if options.verbose { 6 } else { 1 };
+                cells.push(format_ping(&room.ping));
+                cells.extend(std::iter::repeat_n("-".to_string(), stats - 1));
+                cells.push(room.packet_loss.map(format_loss).unwrap_or_default());
+            }
+            if show_sparkline {
+                cells.push(sparkline(&room.replies));
+            }
+            if show_probe {
+                let probe = room.probe.filter(|_| room.ping.is_reachable());
+                cells.push(probe.map(|probe| probe.to_string()).unwrap_or_default());
+            }
+            if let Some(trends) = &options.trends {
+                cells.push(trends.of(room).unwrap_or_default().to_string());
+            }
+            if let Some(changes) = &options.changes {
+                cells.push(changes.of(room).unwrap_or_default().to_string());
+            }
+
+            (room, cells)
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = header
+        .iter()
+        .map(|column| column.width())
+        .collect::<Vec<_>>();
+    for (_, cells) in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    print_notes(options);
+    if hidden > 0 {
+        println!("…and {hidden} more");
+    }
+
+    let header_cells = header
+        .iter()
+        .map(|column| column.to_string())
+        .collect::<Vec<_>>();
+    println!(
+        "{}",
+        join_row(&header_cells, &widths, &right_aligned, |_, cell| cell)
+    );
+
+    let mut group = None;
+    for (room, cells) in rows.iter().rev() {
+        if options.group_by_game {
+            print_group(&mut group, room);
+        }
+
+        let line = join_row(cells, &widths, &right_aligned, |column, cell| {
+            // colored after padding, since the escape codes don't take up any columns
+            match (&options.colors, room.ping.stats(), header[column]) {
+                (Some(colors), Some(ping), "Ping" | "Min") => colors.paint(Some(ping.min), &cell),
+                (Some(colors), Some(ping), "Avg") => colors.paint(Some(ping.avg), &cell),
+                (Some(colors), Some(ping), "Max") => colors.paint(Some(ping.max), &cell),
+                (Some(colors), _, "Name") if room.players.is_empty() => colors.dim(&cell),
+                _ => cell,
+            }
+        });
+        println!("{line}");
+
+        if options.show_description {
+            print_description(room);
+        }
+        if options.show_players {
+            print_players(room);
+        }
+    }
+}
+
+// the heading for the rooms of the next game, once the last of the previous game's
+// rooms has been printed
+fn print_group<'a>(group: &mut Option<&'a str>, room: &'a Room) {
+    if *group != Some(room.game_name.as_str()) {
+        println!("{}:", clean(&room.game_name));
+        *group = Some(&room.game_name);
+    }
+}
+
+fn join_row(
+    cells: &[String],
+    widths: &[usize],
+    right_aligned: &[bool],
+    mut style: impl FnMut(usize, String) -> String,
+) -> String {
+    let cells = cells
+        .iter()
+        .zip(widths)
+        .zip(right_aligned)
+        .enumerate()
+        .map(|(column, ((cell, &width), &right))| {
+            let padding = " ".repeat(width.saturating_sub(cell.width()));
+            let cell = if right {
+                format!("{padding}{cell}")
+            } else {
+                format!("{cell}{padding}")
+            };
+            style(column, cell)
+        })
+        .collect::<Vec<_>>();
+
+    cells.join("  ").trim_end().to_string()
+}
+
+fn print_description(room: &Room) {
+    let description = room.description.as_deref().unwrap_or_default();
+    for line in description
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        println!("    {}", clean(line));
+    }
+}
+
+fn print_players(room: &Room) {
+    for player in &room.players {
+        if player.game == room.game_name {
+            println!("    {}", clean(&player.nickname));
+        } else {
+            println!("    {} ({})", clean(&player.nickname), clean(&player.game));
+        }
+    }
+}
+
+/// `text` with the characters that could mess with the terminal escaped, since names
+/// come from whoever hosts a room. That's control characters, which include the escape
+/// starting color codes and cursor movements, and those that flip the direction of the
+/// text around them.
+pub fn clean(text: &str) -> Cow<'_, str> {
+    let is_unsafe = |c: char| {
+        c.is_control()
+            || matches!(c, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+    };
+    if !text.contains(is_unsafe) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut cleaned = String::new();
+    for c in text.chars() {
+        if is_unsafe(c) {
+            cleaned.extend(c.escape_default());
+        } else {
+            cleaned.push(c);
+        }
+    }
+    Cow::Owned(cleaned)
+}
+
+/// Cut `text` short with an ellipsis so that it takes up at most `max` columns, counting
+/// wide characters like CJK as two.
+fn fit(text: &str, max: usize) -> String {
+    if text.width() <= max {
+        return text.to_string();
+    }
+
+    let mut fitted = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        width += c.width().unwrap_or(0);
+        if width > max - 1 {
+            break;
+        }
+        fitted.push(c);
+    }
+    fitted.push('…');
+    fitted
+}
+
+// `Room` skips the ping when (de)serializing since it isn't part of the lobby schema
+#[derive(Serialize)]
+struct JsonRoom<'a> {
+    name: &'a str,
+    /// The user's own name for the room, see --labels
+    label: Option<&'a str>,
+    game: &'a str,
+    address: &'a str,
+    hostname: Option<&'a str>,
+    country: Option<&'a str>,
+    port: u32,
+    players: usize,
+    locked: bool,
+    /// Whether some players are running a different game than the room is hosting
+    version_mismatch: bool,
+    /// Left out entirely when the rooms weren't pinged
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    ping: Option<JsonPing<'a>>,
+    /// When the lobby listing the room was fetched
+    fetched_at: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonPing<'a> {
+    /// What became of the probes along with the stats or the error, see the `Serialize`
+    /// impl of `ProbeResult`
+    #[serde(flatten)]
+    probe: &'a ProbeResult,
+    packet_loss: Option<f32>,
+}
+
+impl<'a> JsonRoom<'a> {
+    fn new(room: &'a Room, fetched_at: &'a str, pinged: bool) -> Self {
+        JsonRoom {
+            name: &room.name,
+            label: room.label.as_deref(),
+            game: &room.game_name,
+            address: &room.address,
+            hostname: room.hostname.as_deref(),
+            country: room.country.as_deref(),
+            port: room.port,
+            players: room.players.len(),
+            locked: room.has_password,
+            version_mismatch: room.has_version_mismatch(),
+            ping: pinged.then_some(JsonPing {
+                probe: &room.ping,
+                packet_loss: room.packet_loss,
+            }),
+            fetched_at,
+        }
+    }
+}
+
+/// Every key of a room in the JSON output, which `--fields` picks from.
+pub const JSON_FIELDS: [&str; 20] = [
+    "name",
+    "label",
+    "game",
+    "address",
+    "hostname",
+    "country",
+    "port",
+    "players",
+    "locked",
+    "version_mismatch",
+    "status",
+    "ping_ms",
+    "avg_ms",
+    "max_ms",
+    "jitter_ms",
+    "p50_ms",
+    "p95_ms",
+    "error",
+    "packet_loss",
+    "fetched_at",
+];
+
+/// How to print the rooms as JSON.
+pub struct JsonOptions {
+    /// When the lobby was fetched
+    pub fetched_at: SystemTime,
+    /// Whether the rooms were pinged at all, the pings are left out if not
+    pub pinged: bool,
+    /// Indent the output, only for every room at once rather than line by line
+    pub pretty: bool,
+    /// Only these keys of each room, in this order. Every key if empty
+    pub fields: Vec<String>,
+    /// Print the rooms as `{"meta": .., "rooms": [..]}` with these counts, rather than
+    /// as a bare array. Only for every room at once
+    pub meta: Option<JsonMeta>,
+}
+
+/// How many rooms there were before filtering, to go with the JSON output.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct JsonMeta {
+    /// How many rooms the lobby listed
+    pub fetched: usize,
+    /// How many of them were hosting the game
+    pub matched_game: usize,
+}
+
+impl JsonOptions {
+    /// `room` as the JSON output has it, with the keys asked for.
+    pub fn room(&self, room: &Room, fetched_at: &str, country: Option<&str>) -> JsonObject {
+        let mut json = JsonRoom::new(room, fetched_at, self.pinged);
+        json.country = country;
+        let serde_json::Value::Object(mut keys) =
+            serde_json::to_value(json).expect("rooms are always serializable")
+        else {
+            unreachable!("rooms are serialized as objects");
+        };
+
+        let fields = if self.fields.is_empty() {
+            JSON_FIELDS.map(str::to_string).to_vec()
+        } else {
+            self.fields.clone()
+        };
+        let fields = fields.into_iter().filter_map(|field| {
+            let value = keys.remove(&field)?;
+            Some((field, value))
+        });
+        JsonObject(fields.collect())
+    }
+}
+
+// serde_json's own maps are sorted by key, which would lose the order of the fields
+pub struct JsonObject(Vec<(String, serde_json::Value)>);
+
+impl Serialize for JsonObject {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Print every room as a JSON array in the order given, wrapped in an object with the
+/// `meta` of the options when there is one.
+pub fn print_json(rooms: &[Room], options: &JsonOptions) {
+    let fetched_at = timestamp(options.fetched_at);
+    let rooms = rooms
+        .iter()
+        .map(|room| options.room(room, &fetched_at, room.country.as_deref()))
+        .collect::<Vec<_>>();
+    print_json_rooms(rooms, options.meta, options.pretty);
+}
+
+/// Print that there are no rooms to show, the same way [`print_json`] would.
+pub fn print_json_empty(meta: Option<JsonMeta>, pretty: bool) {
+    print_json_rooms(Vec::new(), meta, pretty);
+}
+
+#[derive(Serialize)]
+struct JsonOutput {
+    meta: JsonOutputMeta,
+    rooms: Vec<JsonObject>,
+}
+
+#[derive(Serialize)]
+struct JsonOutputMeta {
+    #[serde(flatten)]
+    counts: JsonMeta,
+    shown: usize,
+}
+
+fn print_json_rooms(rooms: Vec<JsonObject>, meta: Option<JsonMeta>, pretty: bool) {
+    // serialized as they are rather than through a `Value`, which would sort the keys
+    fn to_string(json: &impl Serialize, pretty: bool) -> serde_json::Result<String> {
+        if pretty {
+            serde_json::to_string_pretty(json)
+        } else {
+            serde_json::to_string(json)
+        }
+    }
+
+    let json = match meta {
+        Some(counts) => {
+            let meta = JsonOutputMeta {
+                counts,
+                shown: rooms.len(),
+            };
+            to_string(&JsonOutput { meta, rooms }, pretty)
+        }
+        None => to_string(&rooms, pretty),
+    };
+    println!("{}", json.expect("rooms are always serializable"));
+}
+
+/// Print `room` as a single line of JSON and flush it straight away, for when rooms are
+/// printed as they're pinged. `country` stands in for the room's own.
+pub fn print_json_line(room: &Room, country: Option<&str>, options: &JsonOptions) {
+    let fetched_at = timestamp(options.fetched_at);
+    let json = options.room(room, &fetched_at, country);
+
+    let line = serde_json::to_string(&json).expect("rooms are always serializable");
+    let mut stdout = std::io::stdout().lock();
+    // whoever is reading may well stop at any point
+    let _ = writeln!(stdout, "{line}").and_then(|()| stdout.flush());
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    error: &'a str,
+    code: u8,
+}
+
+/// Print why the run failed as a JSON object with the exit code, so that whatever reads
+/// the JSON output is handed JSON even when there are no rooms to give it.
+pub fn print_json_error(message: &str, code: u8) {
+    let json = JsonError {
+        error: message,
+        code,
+    };
+    let line = serde_json::to_string(&json).expect("errors are always serializable");
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{line}").and_then(|()| stdout.flush());
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    fetched_at: &'a str,
+    rooms: Vec<JsonObject>,
+    summary: ReportSummary<'a>,
+}
+
+#[derive(Serialize)]
+struct ReportSummary<'a> {
+    rooms: usize,
+    players: usize,
+    /// Left out along with the rest when the rooms weren't pinged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reachable: Option<usize>,
+    median_ms: Option<f64>,
+    best: Option<&'a str>,
+    best_ms: Option<f64>,
+}
+
+/// Write every room along with the summary of [`print_summary`] to `path` as one JSON
+/// object. It's written next to `path` first and then moved over it, so that whoever
+/// reads it never sees half a report.
+pub fn write_report(path: &Path, rooms: &[Room], options: &JsonOptions) -> std::io::Result<()> {
+    let fetched_at = timestamp(options.fetched_at);
+    let totals = Totals::of(rooms);
+    let report = Report {
+        fetched_at: &fetched_at,
+        rooms: rooms
+            .iter()
+            .map(|room| options.room(room, &fetched_at, room.country.as_deref()))
+            .collect(),
+        summary: ReportSummary {
+            rooms: totals.rooms,
+            players: totals.players,
+            reachable: options.pinged.then_some(totals.reachable),
+            median_ms: totals.median.map(millis),
+            best: totals.best.map(|(room, _)| room.name.as_str()),
+            best_ms: totals.best.map(|(_, ping)| millis(ping)),
+        },
+    };
+    let json = if options.pretty {
+        serde_json::to_vec_pretty(&report)?
+    } else {
+        serde_json::to_vec(&report)?
+    };
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    std::fs::write(&partial, json)?;
+    std::fs::rename(&partial, path)
+}
+
+#[derive(Serialize)]
+struct CsvRoom<'a> {
+    name: &'a str,
+    game: &'a str,
+    address: &'a str,
+    players: usize,
+    ping_ms: Option<f64>,
+    packet_loss: Option<f32>,
+}
+
+// the csv writer can't leave out columns, so rooms that weren't pinged get their own
+#[derive(Serialize)]
+struct CsvUnpingedRoom<'a> {
+    name: &'a str,
+    game: &'a str,
+    address: &'a str,
+    players: usize,
+}
+
+/// Print every room as CSV with a header row, in the order given. Unreachable rooms get
+/// an empty `ping_ms`, and there are no ping columns at all if the rooms weren't
+/// `pinged`.
+pub fn print_csv(rooms: &[Room], pinged: bool) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+
+    // the header is otherwise only written along with the first room, and a CSV without
+    // one is no CSV at all to whatever reads it
+    if rooms.is_empty() {
+        let header = [
+            "name",
+            "game",
+            "address",
+            "players",
+            "ping_ms",
+            "packet_loss",
+        ];
+        writer.write_record(if pinged { &header[..] } else { &header[..4] })?;
+    }
+
+    for room in rooms {
+        if pinged {
+            writer.serialize(CsvRoom {
+                name: &room.name,
+                game: &room.game_name,
+                address: &room.address,
+                players: room.players.len(),
+                ping_ms: room.ping.stats().map(|ping| millis(ping.min)),
+                packet_loss: room.packet_loss,
+            })?;
+        } else {
+            writer.serialize(CsvUnpingedRoom {
+                name: &room.name,
+                game: &room.game_name,
+                address: &room.address,
+                players: room.players.len(),
+            })?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use crate::ping::Summary;
+use std::time::Duration;
+
+/// Each line of `stdout`, trimmed, so that windows' `\r\n` and the padding some pings
+/// put around their lines don't need minding anywhere else.
+fn lines(stdout: &[u8]) -> impl Iterator<Item = &[u8]> {
+    stdout.split(|&b| b == b'\n').map(<[u8]>::trim_ascii)
+}
+
+/// Like [`lines`], leaving out those that aren't utf-8.
+fn str_lines(stdout: &[u8]) -> impl Iterator<Item = &str> {
+    lines(stdout).filter_map(|line| std::str::from_utf8(line).ok())
+}
+
+// statistics lines also mention the address, but only reply lines carry a
+// latency in the form of `=12ms` or `<1ms`
+fn is_reply_line(line: &[u8]) -> bool {
+    line.windows(2).any(|window| window == b"ms") && line.iter().any(|&b| b == b'=' || b == b'<')
+}
+
+// the address has to stand on its own so that `1.0.0.1` isn't found inside `11.0.0.1`
+fn mentions_address(line: &[u8], ip: &[u8]) -> bool {
+    let is_part_of_address = |b: &u8| b.is_ascii_alphanumeric() || *b == b'.';
+
+    line.windows(ip.len())
+        .enumerate()
+        .filter(|(_, window)| *window == ip)
+        .any(|(i, _)| {
+            let before = i.checked_sub(1).and_then(|i| line.get(i));
+            let after = line.get(i + ip.len());
+            !before.is_some_and(is_part_of_address) && !after.is_some_and(is_part_of_address)
+        })
+}
+
+// windows counts `Reply from 10.0.0.1: Destination host unreachable.` as received, and
+// the address in it is the router that gave up rather than the room. only the english
+// wording is recognized
+fn is_unreachable_line(line: &[u8]) -> bool {
+    let line = line.to_ascii_lowercase();
+    [&b"unreachable"[..], b"ttl expired"]
+        .iter()
+        .any(|needle| line.windows(needle.len()).any(|window| window == *needle))
+}
+
+/// How many replies only said that the room can't be reached.
+pub(crate) fn output_to_unreachable(stdout: &[u8]) -> usize {
+    lines(stdout)
+        .filter(|line| is_unreachable_line(line))
+        .count()
+}
+
+/// The latency of every reply from `ip`, in the order they were received.
+pub(crate) fn output_to_latencies(ip: &[u8], stdout: &[u8]) -> Vec<Duration> {
+    lines(stdout)
+        .filter(|line| !is_unreachable_line(line))
+        .filter(|line| is_reply_line(line) && mentions_address(line, ip))
+        .filter_map(parse_latency_from_line)
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+        .collect()
+}
+
+// the prefix is localized (`time=`, `Zeit=`, `temps=`), so anchor on the trailing
+// unit and walk backwards over the number to the `=` or `<` preceding it
+fn parse_latency_from_line(line: &[u8]) -> Option<f64> {
+    line.windows(2)
+        .enumerate()
+        .filter(|(_, window)| *window == b"ms")
+        .find_map(|(i, _)| {
+            let before = line[..i].trim_ascii_end();
+            let start = before
+                .iter()
+                .rposition(|&b| !b.is_ascii_digit() && b != b'.')
+                .map_or(0, |i| i + 1);
+
+            let number = std::str::from_utf8(&before[start..])
+                .ok()?
+                .parse::<f64>()
+                .ok()?;
+
+            match before[..start].trim_ascii_end().last()? {
+                b'=' => Some(number),
+                // windows reports sub-millisecond replies as `time<1ms`
+                b'<' => Some(number / 2.0),
+                _ => None,
+            }
+        })
+}
+
+// linux and macos print `x% packet loss` while windows prints `(x% loss)`
+pub(crate) fn output_to_packet_loss(stdout: &[u8]) -> Option<f32> {
+    lines(stdout).find_map(|line| {
+        let percent = line.iter().position(|&b| b == b'%')?;
+
+        let rest = &line[percent + 1..];
+        if !rest.starts_with(b" packet loss") && !rest.starts_with(b" loss") {
+            return None;
+        }
+
+        let start = line[..percent]
+            .iter()
+            .rposition(|&b| !b.is_ascii_digit() && b != b'.')
+            .map_or(0, |i| i + 1);
+
+        std::str::from_utf8(&line[start..percent])
+            .ok()?
+            .parse()
+            .ok()
+    })
+}
+
+// some busybox builds don't print a latency per reply at all, only the summary at the
+// end, like `round-trip min/avg/max = 11.9/12.4/13.1 ms`. linux and macos print one
+// too, with a fourth statistic
+pub(crate) fn output_to_summary_avg(stdout: &[u8]) -> Option<Duration> {
+    let line = str_lines(stdout).find(|line| line.contains("min/avg/max"))?;
+
+    let (labels, values) = line.split_once('=')?;
+    let labels = labels.split_whitespace().last()?;
+    let avg = labels.split('/').position(|label| label == "avg")?;
+
+    let values = values.trim().strip_suffix("ms")?;
+    let ms = values.split('/').nth(avg)?.trim().parse::<f64>().ok()?;
+    Some(Duration::from_secs_f64(ms / 1000.0))
+}
+
+// windows sums up with `Minimum = 10ms, Maximum = 40ms, Average = 20ms`, which is more
+// dependable than picking each reply out of its output. only the english wording is
+// recognized, the per-reply latencies are still there for the rest
+pub(crate) fn output_to_windows_summary(stdout: &[u8]) -> Option<Summary> {
+    let line = str_lines(stdout).find(|line| line.contains("Minimum = "))?;
+
+    let stat = |name: &str| {
+        let (_, rest) = line.split_once(name)?;
+        let (ms, _) = rest.trim_start().split_once("ms")?;
+        let ms = ms.trim().parse::<f64>().ok()?;
+        Some(Duration::from_secs_f64(ms / 1000.0))
+    };
+
+    Some(Summary {
+        min: stat("Minimum =")?,
+        avg: stat("Average =")?,
+        max: stat("Maximum =")?,
+    })
+}
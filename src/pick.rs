@@ -0,0 +1,79 @@
+use crate::output::{clean, format_ping, occupancy, tags};
+use dialoguer::Select;
+use yuzuping::Room;
+
+/// What to do with the picked room.
+pub enum Action {
+    /// Copy its address and port to the clipboard
+    Copy,
+    /// Join it through the launch command
+    Launch,
+}
+
+/// Let the user pick a room with the arrow keys, closest first, and then what to do with
+/// it. `None` if they backed out with escape.
+pub fn pick(rooms: &[Room], can_launch: bool) -> dialoguer::Result<Option<(&Room, Action)>> {
+    let items = rooms.iter().map(|room| {
+        let tags = tags(room);
+        let ping = format_ping(&room.ping);
+        format!(
+            "{}{tags} ({} playing)  {ping}",
+            clean(&room.name),
+            occupancy(room)
+        )
+    });
+
+    let Some(index) = Select::new()
+        .with_prompt("Pick a room, or escape to quit")
+        .items(items)
+        .default(0)
+        .interact_opt()?
+    else {
+        return Ok(None);
+    };
+
+    if !can_launch {
+        return Ok(Some((&rooms[index], Action::Copy)));
+    }
+
+    let action = Select::new()
+        .with_prompt(format!("{}:", clean(&rooms[index].name)))
+        .items(["Copy the address", "Join it"])
+        .default(0)
+        .interact_opt()?;
+
+    Ok(action.map(|action| {
+        let action = if action == 0 {
+            Action::Copy
+        } else {
+            Action::Launch
+        };
+        (&rooms[index], action)
+    }))
+}
+
+/// Let the user pick one of the hosted `games`, listed like --list-games does, starting
+/// on `default`. `None` if they backed out.
+pub fn pick_game(
+    prompt: &str,
+    games: &[(&str, usize)],
+    default: usize,
+) -> dialoguer::Result<Option<usize>> {
+    let items = games
+        .iter()
+        .map(|&(game, rooms)| format!("{rooms:>4}  {}", clean(game)));
+
+    Select::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(default)
+        .interact_opt()
+}
+
+/// Put `text` on the clipboard.
+///
+/// On linux the clipboard is only served while we're running, which is fine as long as
+/// the exit prompt keeps us around until it's been pasted.
+pub fn copy(text: &str) -> Result<(), arboard::Error> {
+    arboard::Clipboard::new()?.set_text(text)
+}
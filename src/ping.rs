@@ -0,0 +1,529 @@
+#[cfg(feature = "icmp")]
+use crate::icmp::IcmpPinger;
+use crate::{group, parse, Error};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// What came back from probing a room.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replies {
+    /// The latency of every reply, in the order they were received.
+    pub latencies: Vec<Duration>,
+    /// Percentage of probes that went unanswered, if known.
+    pub packet_loss: Option<f32>,
+    /// The statistics as the probe itself reported them, if it did.
+    pub summary: Option<Summary>,
+    /// Which probe got the replies, when pinging with more than one.
+    pub probe: Option<Probe>,
+    /// Whether the probes were told the room can't be reached, rather than just going
+    /// unanswered.
+    pub unreachable: bool,
+}
+
+/// The minimum, average and maximum latency of the replies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+}
+
+impl Replies {
+    /// The replies to `sent` probes, where every probe not in `latencies` was lost.
+    pub fn from_probes(latencies: Vec<Duration>, sent: u8) -> Self {
+        let lost = (sent as usize).saturating_sub(latencies.len());
+        Replies {
+            packet_loss: Some(lost as f32 / sent.max(1) as f32 * 100.0),
+            latencies,
+            summary: None,
+            probe: None,
+            unreachable: false,
+        }
+    }
+
+    /// The replies to the `sent` probes that count, leaving out the first one, to a
+    /// probe sent ahead of them to warm up the route. A lone reply is kept, since it
+    /// might as well be to one of the probes that count.
+    pub fn without_warmup(mut self, sent: u8) -> Self {
+        if self.latencies.len() < 2 {
+            return self;
+        }
+        self.latencies.remove(0);
+        Replies {
+            probe: self.probe,
+            ..Replies::from_probes(self.latencies, sent)
+        }
+    }
+
+    /// The replies from `ip` in what the system `ping` printed after sending `sent`
+    /// probes.
+    pub fn from_ping_output(ip: &IpAddr, stdout: &[u8], sent: u8) -> Self {
+        let summary = parse::output_to_windows_summary(stdout);
+
+        let mut latencies = parse::output_to_latencies(ip.to_string().as_bytes(), stdout);
+        if latencies.is_empty() {
+            // the average stands in for every reply when they weren't printed one by one
+            let avg = summary.map(|summary| summary.avg);
+            latencies.extend(avg.or_else(|| parse::output_to_summary_avg(stdout)));
+        }
+
+        let replies = if parse::output_to_unreachable(stdout) > 0 {
+            // the summary counts those replies as received, so count the losses ourselves
+            Replies {
+                unreachable: true,
+                ..Replies::from_probes(latencies, sent)
+            }
+        } else {
+            Replies {
+                latencies,
+                packet_loss: parse::output_to_packet_loss(stdout),
+                summary: None,
+                probe: None,
+                unreachable: false,
+            }
+        };
+        Replies { summary, ..replies }
+    }
+}
+
+/// Measures the latency to a room.
+pub trait Pinger {
+    /// Probe the room at `ip`. Only some probes make use of the room's game `port`.
+    fn ping(&self, ip: &IpAddr, port: u16) -> impl Future<Output = Result<Replies, Error>> + Send;
+}
+
+/// Which kind of probe to measure latency with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Probe {
+    /// ICMP echo through the system `ping` command.
+    #[default]
+    Icmp,
+    /// Connecting over TCP to the room's game port, for hosts that drop ICMP.
+    Tcp,
+    /// A datagram to the room's game port, see [`UdpPinger`].
+    Udp,
+    /// ICMP echo sent directly, see [`IcmpPinger`].
+    #[cfg(feature = "icmp")]
+    Native,
+    /// ICMP through the system `ping` and TCP at the same time, see [`BothPinger`].
+    Both,
+}
+
+impl std::fmt::Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Probe::Icmp => "icmp",
+            Probe::Tcp => "tcp",
+            Probe::Udp => "udp",
+            #[cfg(feature = "icmp")]
+            Probe::Native => "native",
+            Probe::Both => "both",
+        })
+    }
+}
+
+/// Pings with a [`Probe`] chosen at runtime.
+#[derive(Debug)]
+pub enum ProbePinger {
+    Icmp(SubprocessPinger),
+    Tcp(TcpPinger),
+    Udp(UdpPinger),
+    #[cfg(feature = "icmp")]
+    Native(IcmpPinger),
+    Both(BothPinger<SubprocessPinger, TcpPinger>),
+}
+
+impl ProbePinger {
+    /// Set up the pinger for `probe`. Only the native ICMP probe can fail, when it isn't
+    /// allowed to open an ICMP socket.
+    pub fn new(probe: Probe, count: u8, timeout: Duration) -> Result<Self, Error> {
+        Ok(match probe {
+            Probe::Icmp => ProbePinger::Icmp(SubprocessPinger::new(count, timeout)),
+            Probe::Tcp => ProbePinger::Tcp(TcpPinger::new(count, timeout)),
+            Probe::Udp => ProbePinger::Udp(UdpPinger::new(count, timeout)),
+            #[cfg(feature = "icmp")]
+            Probe::Native => ProbePinger::Native(IcmpPinger::new(count, timeout)?),
+            Probe::Both => ProbePinger::Both(BothPinger {
+                preferred: (Probe::Icmp, SubprocessPinger::new(count, timeout)),
+                fallback: (Probe::Tcp, TcpPinger::new(count, timeout)),
+            }),
+        })
+    }
+}
+
+impl Pinger for ProbePinger {
+    async fn ping(&self, ip: &IpAddr, port: u16) -> Result<Replies, Error> {
+        match self {
+            ProbePinger::Icmp(pinger) => pinger.ping(ip, port).await,
+            ProbePinger::Tcp(pinger) => pinger.ping(ip, port).await,
+            ProbePinger::Udp(pinger) => pinger.ping(ip, port).await,
+            #[cfg(feature = "icmp")]
+            ProbePinger::Native(pinger) => pinger.ping(ip, port).await,
+            ProbePinger::Both(pinger) => pinger.ping(ip, port).await,
+        }
+    }
+}
+
+/// Pings with two probes at the same time, for hosts that drop one kind of probe or the
+/// other. The replies are labeled with the probe they're from, which is the preferred
+/// one whenever both got some.
+#[derive(Debug)]
+pub struct BothPinger<A, B> {
+    pub preferred: (Probe, A),
+    pub fallback: (Probe, B),
+}
+
+impl<A: Pinger + Sync, B: Pinger + Sync> Pinger for BothPinger<A, B> {
+    async fn ping(&self, ip: &IpAddr, port: u16) -> Result<Replies, Error> {
+        let (preferred_probe, preferred) = &self.preferred;
+        let (fallback_probe, fallback) = &self.fallback;
+        let (preferred, fallback) = tokio::join!(preferred.ping(ip, port), fallback.ping(ip, port));
+
+        let label = |probe: Probe| {
+            move |replies: Replies| Replies {
+                probe: Some(probe),
+                ..replies
+            }
+        };
+        let replied = |result: &Result<Replies, Error>| {
+            result
+                .as_ref()
+                .is_ok_and(|replies| !replies.latencies.is_empty())
+        };
+
+        if replied(&preferred) || (preferred.is_ok() && fallback.is_err()) {
+            if let Err(err) = &fallback {
+                tracing::debug!(%ip, "the {fallback_probe} probe failed: {err}");
+            }
+            return preferred.map(label(*preferred_probe));
+        }
+        if let Err(err) = &preferred {
+            tracing::debug!(%ip, "the {preferred_probe} probe failed: {err}");
+        }
+        fallback.map(label(*fallback_probe))
+    }
+}
+
+/// Pings with a second probe only when the first gets no replies, unlike
+/// [`BothPinger`], or only with the first when there's no `fallback`. The replies of the
+/// fallback are labeled with its probe.
+#[derive(Debug)]
+pub struct FallbackPinger<A, B> {
+    pub primary: (Probe, A),
+    pub fallback: Option<(Probe, B)>,
+}
+
+impl<A: Pinger + Sync, B: Pinger + Sync> Pinger for FallbackPinger<A, B> {
+    async fn ping(&self, ip: &IpAddr, port: u16) -> Result<Replies, Error> {
+        let (primary_probe, primary) = &self.primary;
+        let Some((fallback_probe, fallback)) = &self.fallback else {
+            return primary.ping(ip, port).await;
+        };
+
+        let err = match primary.ping(ip, port).await {
+            Ok(replies) if !replies.latencies.is_empty() => return Ok(replies),
+            Ok(_) => None,
+            // there's no pinging without it, which the fallback doesn't change
+            Err(err @ Error::PingNotFound(_)) => return Err(err),
+            Err(err) => Some(err),
+        };
+        match &err {
+            Some(err) => tracing::debug!(%ip, "the {primary_probe} probe failed: {err}"),
+            None => tracing::debug!(%ip, "no replies to the {primary_probe} probe"),
+        }
+
+        let replies = fallback.ping(ip, port).await?;
+        let probe = replies.probe.or(Some(*fallback_probe));
+        Ok(Replies { probe, ..replies })
+    }
+}
+
+/// Pings by spawning the system `ping` command and parsing what it prints.
+#[derive(Debug, Clone)]
+pub struct SubprocessPinger {
+    /// How many echo requests to send.
+    pub count: u8,
+    /// How long to wait for each reply.
+    pub timeout: Duration,
+    /// Run this instead of the usual `ping` invocation for the platform.
+    pub command: Option<PingCommand>,
+}
+
+impl SubprocessPinger {
+    pub fn new(count: u8, timeout: Duration) -> Self {
+        SubprocessPinger {
+            count,
+            timeout,
+            command: None,
+        }
+    }
+
+    pub fn with_command(self, command: PingCommand) -> Self {
+        SubprocessPinger {
+            command: Some(command),
+            ..self
+        }
+    }
+}
+
+/// A command line to ping with in place of the system `ping`, like
+/// `fping -c {count} -t {timeout} {address}`. Its output has to look like one of the
+/// `ping`s that are understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl PingCommand {
+    /// Split `template` on whitespace into the program and its arguments. `{address}`
+    /// has to be among them, `{count}` and `{timeout}` in milliseconds are optional.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut words = template.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or("the ping command is empty")?;
+        let args = words.collect::<Vec<_>>();
+
+        if !args.iter().any(|arg| arg.contains("{address}")) {
+            return Err(
+                "the ping command has to pass the {address} somewhere, it can also use \
+                 {count} and {timeout}"
+                    .to_string(),
+            );
+        }
+
+        Ok(PingCommand { program, args })
+    }
+
+    fn command(&self, ip: IpAddr, count: u8, timeout: Duration) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(self.args.iter().map(|arg| {
+            arg.replace("{address}", &ip.to_string())
+                .replace("{count}", &count.to_string())
+                .replace("{timeout}", &timeout.as_millis().to_string())
+        }));
+        command
+    }
+}
+
+impl Default for SubprocessPinger {
+    fn default() -> Self {
+        SubprocessPinger::new(3, Duration::from_millis(500))
+    }
+}
+
+impl Pinger for SubprocessPinger {
+    async fn ping(&self, ip: &IpAddr, _: u16) -> Result<Replies, Error> {
+        // ping waits about a second between requests, but it can hang for far longer than
+        // that on some networks. giving up drops the child, which kills it and its group.
+        let deadline = (Duration::from_secs(1) + self.timeout) * self.count as u32;
+        let output = async {
+            match &self.command {
+                Some(command) => {
+                    group::output(command.command(*ip, self.count, self.timeout)).await
+                }
+                None => ping(*ip, self.count, self.timeout).await,
+            }
+        };
+        let output = tokio::time::timeout(deadline, output)
+            .await
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "ping didn't finish in time")
+            })
+            .and_then(|output| output)
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Error::PingNotFound(match &self.command {
+                    Some(command) => command.program.clone(),
+                    None => "ping".to_string(),
+                }),
+                _ => Error::Ping(err),
+            })?;
+
+        tracing::trace!(
+            %ip,
+            stdout = %String::from_utf8_lossy(&output.stdout),
+            stderr = %String::from_utf8_lossy(&output.stderr),
+            "ping finished"
+        );
+
+        let replies = Replies::from_ping_output(ip, &output.stdout, self.count);
+
+        // even an unreachable host gets a summary, so this is most likely ping complaining
+        if replies.latencies.is_empty() && replies.packet_loss.is_none() {
+            let complaint = if output.stderr.is_empty() {
+                &output.stdout
+            } else {
+                &output.stderr
+            };
+            let complaint = String::from_utf8_lossy(complaint);
+            let first_line = complaint.trim().lines().next().unwrap_or_default();
+            return Err(Error::Parse(first_line.to_string()));
+        }
+
+        Ok(replies)
+    }
+}
+
+/// Pings by timing how long it takes to open a TCP connection to the room's port.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpPinger {
+    /// How many connections to open, one after the other.
+    pub count: u8,
+    /// How long to wait for each connection.
+    pub timeout: Duration,
+}
+
+impl TcpPinger {
+    pub fn new(count: u8, timeout: Duration) -> Self {
+        TcpPinger { count, timeout }
+    }
+}
+
+impl Pinger for TcpPinger {
+    async fn ping(&self, ip: &IpAddr, port: u16) -> Result<Replies, Error> {
+        let addr = SocketAddr::new(*ip, port);
+        let mut latencies = Vec::new();
+        let mut refused = false;
+
+        for _ in 0..self.count {
+            let start = Instant::now();
+            let connect = tokio::net::TcpStream::connect(addr);
+            match tokio::time::timeout(self.timeout, connect).await {
+                Ok(Ok(_)) => latencies.push(start.elapsed()),
+                Ok(Err(_)) => refused = true,
+                Err(_) => {}
+            }
+        }
+
+        Ok(Replies {
+            // turned away rather than left hanging
+            unreachable: refused && latencies.is_empty(),
+            ..Replies::from_probes(latencies, self.count)
+        })
+    }
+}
+
+/// Pings by timing how long it takes the room's port to answer a datagram.
+///
+/// This is best-effort: the game servers don't answer datagrams they don't understand,
+/// so only hosts that send back an ICMP port unreachable, or happen to reply, count as
+/// reachable. Rooms that stay silent are reported unreachable even if they can be
+/// joined.
+#[derive(Debug, Clone, Copy)]
+pub struct UdpPinger {
+    /// How many datagrams to send, one after the other.
+    pub count: u8,
+    /// How long to wait for each answer.
+    pub timeout: Duration,
+}
+
+impl UdpPinger {
+    pub fn new(count: u8, timeout: Duration) -> Self {
+        UdpPinger { count, timeout }
+    }
+}
+
+impl Pinger for UdpPinger {
+    async fn ping(&self, ip: &IpAddr, port: u16) -> Result<Replies, Error> {
+        let addr = SocketAddr::new(*ip, port);
+        let mut latencies = Vec::new();
+
+        // a socket per probe so that a late answer isn't taken for the next one's
+        for _ in 0..self.count {
+            let start = Instant::now();
+            let probe = async {
+                let socket = bind_udp(addr).await?;
+                socket.connect(addr).await?;
+                socket.send(b"yuzuping").await?;
+                socket.recv(&mut [0; 512]).await
+            };
+
+            match tokio::time::timeout(self.timeout, probe).await {
+                // an ICMP port unreachable comes back as an error on the connected
+                // socket, connection refused on unix and reset on windows
+                Ok(Ok(_)) => latencies.push(start.elapsed()),
+                Ok(Err(err))
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    latencies.push(start.elapsed())
+                }
+                Ok(Err(err)) => return Err(Error::Ping(err)),
+                Err(_) => {}
+            }
+        }
+
+        Ok(Replies::from_probes(latencies, self.count))
+    }
+}
+
+async fn bind_udp(addr: SocketAddr) -> std::io::Result<tokio::net::UdpSocket> {
+    let local = match addr {
+        SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+        SocketAddr::V6(_) => SocketAddr::from(([0; 16], 0)),
+    };
+    tokio::net::UdpSocket::bind(local).await
+}
+
+// we spawn a subshell instead of using ICMP directly because then we don't require
+// sudo/administrator or setcap.
+//
+// the child is killed along with whatever it started if the future is dropped, so that
+// cancelling a run doesn't leave pings behind.
+
+#[cfg(windows)]
+fn ping(
+    ip: IpAddr,
+    count: u8,
+    timeout: Duration,
+) -> impl Future<Output = Result<std::process::Output, std::io::Error>> {
+    let mut command = tokio::process::Command::new("ping");
+    if ip.is_ipv6() {
+        command.arg("-6");
+    }
+    command
+        .arg("-n")
+        .arg(count.to_string())
+        .arg("-w")
+        .arg(timeout.as_millis().to_string())
+        .arg(ip.to_string());
+    group::output(command)
+}
+#[cfg(target_os = "macos")]
+fn ping(
+    ip: IpAddr,
+    count: u8,
+    timeout: Duration,
+) -> impl Future<Output = Result<std::process::Output, std::io::Error>> {
+    // macos has a separate binary for IPv6 which doesn't take a wait time, and the
+    // -W of the regular ping is in milliseconds rather than seconds like on linux
+    let mut command = if ip.is_ipv6() {
+        tokio::process::Command::new("ping6")
+    } else {
+        let mut command = tokio::process::Command::new("ping");
+        command.arg("-W").arg(timeout.as_millis().to_string());
+        command
+    };
+    command.arg("-c").arg(count.to_string()).arg(ip.to_string());
+    group::output(command)
+}
+#[cfg(not(any(windows, target_os = "macos")))]
+fn ping(
+    ip: IpAddr,
+    count: u8,
+    timeout: Duration,
+) -> impl Future<Output = Result<std::process::Output, std::io::Error>> {
+    let mut command = tokio::process::Command::new("ping");
+    if ip.is_ipv6() {
+        command.arg("-6");
+    }
+    command
+        .arg("-c")
+        .arg(count.to_string())
+        .arg("-W")
+        .arg(timeout.as_secs_f64().to_string())
+        .arg(ip.to_string());
+    group::output(command)
+}
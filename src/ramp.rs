@@ -0,0 +1,111 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// How many rooms an adaptive concurrency starts out pinging at a time.
+const START: usize = 4;
+
+/// The pings let through at once with an adaptive concurrency, see
+/// [`PingConfig::adaptive`](crate::PingConfig::adaptive). Every time as many pings as
+/// are let through finished, that many more are let through, unless the pings took
+/// noticeably longer or timed out noticeably more often than before, in which case only
+/// half as many are.
+#[derive(Debug)]
+pub(crate) struct Ramp {
+    permits: Semaphore,
+    max: usize,
+    batch: Mutex<Batch>,
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    /// How many pings are let through at once
+    granted: usize,
+    /// The permits to forget as they come back, from backing off while they were out
+    owed: usize,
+    done: usize,
+    took: Duration,
+    timeouts: usize,
+    /// The quickest a batch took on average so far
+    quickest: Option<Duration>,
+    /// The share of the pings of the batch before that timed out
+    timed_out_before: Option<f64>,
+}
+
+impl Ramp {
+    /// Let through no more than `max` pings at a time.
+    pub(crate) fn new(max: usize) -> Self {
+        let granted = START.min(max).max(1);
+        Ramp {
+            permits: Semaphore::new(granted),
+            max: max.max(1),
+            batch: Mutex::new(Batch {
+                granted,
+                ..Batch::default()
+            }),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.permits
+            .acquire()
+            .await
+            .expect("the semaphore is never closed")
+    }
+
+    /// Hand back the `permit` of a ping that took `took`, and whether it timed out.
+    pub(crate) fn release(&self, permit: SemaphorePermit<'_>, took: Duration, timed_out: bool) {
+        let mut batch = self
+            .batch
+            .lock()
+            .expect("nothing panics while holding the lock");
+        if batch.owed > 0 {
+            batch.owed -= 1;
+            permit.forget();
+        } else {
+            drop(permit);
+        }
+
+        batch.done += 1;
+        batch.took += took;
+        batch.timeouts += usize::from(timed_out);
+        if batch.done < batch.granted {
+            return;
+        }
+
+        let took = batch.took / batch.done as u32;
+        let timed_out = batch.timeouts as f64 / batch.done as f64;
+        // a few rooms timing out is just the lobby, a lot more than before is the network
+        let spiked = batch
+            .timed_out_before
+            .is_some_and(|before| timed_out > before + 0.25);
+        let slowed = batch
+            .quickest
+            .is_some_and(|quickest| took > quickest.mul_f64(1.5));
+
+        let granted = batch.granted;
+        if spiked || slowed {
+            let fewer = (granted / 2).max(START.min(self.max));
+            batch.owed += granted - fewer;
+            batch.granted = fewer;
+        } else if granted < self.max {
+            let more = granted.min(self.max - granted);
+            self.permits.add_permits(more);
+            batch.granted += more;
+        }
+        if batch.granted != granted {
+            tracing::debug!(
+                ?took,
+                timed_out,
+                "pinging {} rooms at a time",
+                batch.granted
+            );
+        }
+
+        batch.quickest = Some(batch.quickest.map_or(took, |quickest| quickest.min(took)));
+        batch.timed_out_before = Some(timed_out);
+        batch.done = 0;
+        batch.took = Duration::ZERO;
+        batch.timeouts = 0;
+    }
+}
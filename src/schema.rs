@@ -0,0 +1,234 @@
+use crate::{Player, Response, Room};
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::io::{BufReader, Read};
+
+/// Which emulator's lobby API a lobby speaks. They all end up as the same [`Room`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LobbySchema {
+    /// `{"rooms": [...]}` with camelCase fields, which [`Room`] deserializes from as is.
+    #[default]
+    Yuzu,
+    /// yuzu's schema, as served by suyu's lobby, whose fields aren't always cased the
+    /// same.
+    Suyu,
+    /// A bare array of games with snake_case fields, and the players listed by nickname
+    /// only.
+    Ryujinx,
+}
+
+impl LobbySchema {
+    pub(crate) fn parse(self, json: &[u8]) -> serde_json::Result<Response> {
+        match self {
+            LobbySchema::Yuzu => serde_json::from_slice(json),
+            LobbySchema::Suyu => {
+                let resp = serde_json::from_slice::<SuyuResponse>(json)?;
+                Ok(Response {
+                    rooms: resp.rooms.into_iter().map(Room::from).collect(),
+                })
+            }
+            LobbySchema::Ryujinx => {
+                let games = serde_json::from_slice::<Vec<RyujinxGame>>(json)?;
+                Ok(Response {
+                    rooms: games.into_iter().map(Room::from).collect(),
+                })
+            }
+        }
+    }
+
+    /// Parse the lobby `reader` reads, keeping only the rooms `keep` returns true for.
+    /// yuzu's lobby is parsed a room at a time as it's read, so that the rest are
+    /// dropped right away rather than all held at once. The other schemas are read in
+    /// whole and filtered afterwards.
+    pub(crate) fn parse_filtered(
+        self,
+        reader: impl Read,
+        mut keep: impl FnMut(&Room) -> bool,
+    ) -> serde_json::Result<Response> {
+        let mut reader = BufReader::new(reader);
+        match self {
+            LobbySchema::Yuzu => {
+                let mut deserializer = serde_json::Deserializer::from_reader(reader);
+                let rooms = FilteredResponse(&mut keep).deserialize(&mut deserializer)?;
+                deserializer.end()?;
+                Ok(Response { rooms })
+            }
+            schema => {
+                let mut json = Vec::new();
+                reader
+                    .read_to_end(&mut json)
+                    .map_err(serde_json::Error::io)?;
+                let mut resp = schema.parse(&json)?;
+                resp.rooms.retain(|room| keep(room));
+                Ok(resp)
+            }
+        }
+    }
+}
+
+/// `{"rooms": [...]}`, keeping the rooms that the function returns true for.
+struct FilteredResponse<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(&Room) -> bool> DeserializeSeed<'de> for FilteredResponse<'_, F> {
+    type Value = Vec<Room>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<Room>, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(&Room) -> bool> Visitor<'de> for FilteredResponse<'_, F> {
+    type Value = Vec<Room>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a lobby with a list of rooms")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Vec<Room>, A::Error> {
+        let mut rooms = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "rooms" && rooms.is_none() {
+                rooms = Some(map.next_value_seed(FilteredRooms(&mut *self.0))?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        rooms.ok_or_else(|| serde::de::Error::missing_field("rooms"))
+    }
+}
+
+struct FilteredRooms<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(&Room) -> bool> DeserializeSeed<'de> for FilteredRooms<'_, F> {
+    type Value = Vec<Room>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<Room>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(&Room) -> bool> Visitor<'de> for FilteredRooms<'_, F> {
+    type Value = Vec<Room>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a list of rooms")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<Room>, A::Error> {
+        let mut rooms = Vec::new();
+        while let Some(room) = seq.next_element::<Room>()? {
+            if (self.0)(&room) {
+                rooms.push(room);
+            }
+        }
+        Ok(rooms)
+    }
+}
+
+#[derive(Deserialize)]
+struct SuyuResponse {
+    rooms: Vec<SuyuRoom>,
+}
+
+#[derive(Deserialize)]
+struct SuyuRoom {
+    #[serde(default)]
+    port: u32,
+    #[serde(default)]
+    name: String,
+    description: Option<String>,
+    #[serde(
+        default,
+        alias = "preferredGameName",
+        alias = "preferredgamename",
+        alias = "PreferredGameName"
+    )]
+    preferred_game_name: String,
+    #[serde(default)]
+    address: String,
+    #[serde(default)]
+    players: Vec<SuyuPlayer>,
+    #[serde(default, alias = "maxPlayers", alias = "MaxPlayers")]
+    max_players: Option<u32>,
+    #[serde(default, alias = "hasPassword", alias = "HasPassword")]
+    has_password: bool,
+}
+
+#[derive(Deserialize)]
+struct SuyuPlayer {
+    #[serde(default)]
+    nickname: String,
+    #[serde(default, alias = "gameName", alias = "gamename", alias = "GameName")]
+    game_name: String,
+}
+
+impl From<SuyuRoom> for Room {
+    fn from(room: SuyuRoom) -> Self {
+        Room {
+            port: room.port,
+            name: room.name,
+            description: room.description,
+            game_name: room.preferred_game_name,
+            address: room.address,
+            players: room
+                .players
+                .into_iter()
+                .map(|player| Player {
+                    nickname: player.nickname,
+                    game: player.game_name,
+                })
+                .collect(),
+            max_players: room.max_players,
+            has_password: room.has_password,
+            ..Room::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RyujinxGame {
+    #[serde(default, alias = "name")]
+    room_name: String,
+    #[serde(default)]
+    game_name: String,
+    #[serde(default, alias = "ip")]
+    address: String,
+    #[serde(default)]
+    port: u32,
+    #[serde(default)]
+    players: Vec<String>,
+    #[serde(default)]
+    max_player_count: Option<u32>,
+    #[serde(default, alias = "has_password")]
+    is_password_protected: bool,
+}
+
+impl From<RyujinxGame> for Room {
+    fn from(game: RyujinxGame) -> Self {
+        // every player is in the game the room is hosting, there's no telling otherwise
+        let players = game
+            .players
+            .into_iter()
+            .map(|nickname| Player {
+                nickname,
+                game: game.game_name.clone(),
+            })
+            .collect();
+
+        Room {
+            port: game.port,
+            // rooms aren't always named, the game is the next best thing
+            name: if game.room_name.is_empty() {
+                game.game_name.clone()
+            } else {
+                game.room_name
+            },
+            game_name: game.game_name,
+            address: game.address,
+            players,
+            max_players: game.max_player_count,
+            has_password: game.is_password_protected,
+            ..Room::default()
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::time::Duration;
+
+/// Summary of the replies received from a room.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PingStats {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    /// Mean difference between consecutive replies.
+    pub jitter: Duration,
+    /// Half of the replies were at least this fast.
+    pub p50: Duration,
+    /// All but the slowest 5% of the replies were at least this fast.
+    pub p95: Duration,
+}
+
+impl PingStats {
+    /// Summarize the latencies of the replies in the order they were received, or
+    /// `None` if there weren't any.
+    pub fn from_replies(replies: &[Duration]) -> Option<PingStats> {
+        let min = *replies.iter().min()?;
+        let max = *replies.iter().max()?;
+        let avg = replies.iter().sum::<Duration>() / replies.len() as u32;
+
+        let jitter = match replies.len() {
+            1 => Duration::ZERO,
+            n => {
+                replies
+                    .windows(2)
+                    .map(|pair| pair[0].abs_diff(pair[1]))
+                    .sum::<Duration>()
+                    / (n - 1) as u32
+            }
+        };
+
+        let mut sorted = replies.to_vec();
+        sorted.sort();
+
+        Some(PingStats {
+            min,
+            avg,
+            max,
+            jitter,
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+        })
+    }
+}
+
+// nearest rank, so that it's always one of the replies rather than in between two
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+/// How probing a room went.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ProbeResult {
+    /// Not probed, or not yet.
+    #[default]
+    Pending,
+    Reachable(PingStats),
+    /// None of the probes were answered.
+    Timeout,
+    /// The probes were told the room can't be reached, by a router or the room's host.
+    Unreachable,
+    /// The room's address didn't resolve to an IP.
+    ResolveFailed,
+    /// Probing failed, for this reason.
+    Error(String),
+}
+
+impl ProbeResult {
+    /// The summary of the replies, if the room replied.
+    pub fn stats(&self) -> Option<PingStats> {
+        match self {
+            ProbeResult::Reachable(stats) => Some(*stats),
+            _ => None,
+        }
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        matches!(self, ProbeResult::Reachable(_))
+    }
+
+    /// What became of the probes in a word, like `timeout`.
+    pub fn status(&self) -> &'static str {
+        match self {
+            ProbeResult::Pending => "pending",
+            ProbeResult::Reachable(_) => "reachable",
+            ProbeResult::Timeout => "timeout",
+            ProbeResult::Unreachable => "unreachable",
+            ProbeResult::ResolveFailed => "resolve_failed",
+            ProbeResult::Error(_) => "error",
+        }
+    }
+}
+
+// as `{"status": "reachable", "ping_ms": 12.3, .., "error": null}`, with the stats of a
+// reachable room in fractional milliseconds, `ping_ms` being the minimum, and the reason
+// of an error as `error`. every key is there whatever the status, so that the shape of
+// a room stays the same
+impl Serialize for ProbeResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let stats = self.stats();
+        let ms = |stat: fn(&PingStats) -> Duration| {
+            stats
+                .as_ref()
+                .map(|stats| stat(stats).as_secs_f64() * 1000.0)
+        };
+        let error = match self {
+            ProbeResult::Error(reason) => Some(reason.as_str()),
+            _ => None,
+        };
+
+        let mut map = serializer.serialize_map(Some(8))?;
+        map.serialize_entry("status", self.status())?;
+        map.serialize_entry("ping_ms", &ms(|stats| stats.min))?;
+        map.serialize_entry("avg_ms", &ms(|stats| stats.avg))?;
+        map.serialize_entry("max_ms", &ms(|stats| stats.max))?;
+        map.serialize_entry("jitter_ms", &ms(|stats| stats.jitter))?;
+        map.serialize_entry("p50_ms", &ms(|stats| stats.p50))?;
+        map.serialize_entry("p95_ms", &ms(|stats| stats.p95))?;
+        map.serialize_entry("error", &error)?;
+        map.end()
+    }
+}
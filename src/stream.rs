@@ -0,0 +1,46 @@
+use std::io::Read;
+use tokio::sync::mpsc::Receiver;
+
+/// Reads the chunks of a response body as they're received, so that it can be parsed on
+/// a blocking thread while the rest is still being downloaded.
+pub(crate) struct ChunkReader<T> {
+    chunks: Receiver<T>,
+    chunk: Option<T>,
+    /// How much of `chunk` was read already
+    offset: usize,
+}
+
+impl<T> ChunkReader<T> {
+    pub(crate) fn new(chunks: Receiver<T>) -> Self {
+        ChunkReader {
+            chunks,
+            chunk: None,
+            offset: 0,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for ChunkReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let rest = &chunk.as_ref()[self.offset..];
+                if !rest.is_empty() {
+                    let read = rest.len().min(buf.len());
+                    buf[..read].copy_from_slice(&rest[..read]);
+                    self.offset += read;
+                    return Ok(read);
+                }
+            }
+
+            // the body ends when the sender is dropped
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk);
+                    self.offset = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+}
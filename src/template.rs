@@ -0,0 +1,187 @@
+use crate::output::{clean, format_latency, format_loss, format_ping, host, name, PlainOptions};
+use std::time::Duration;
+use yuzuping::Room;
+
+/// A line to print for each room, like `{name} {players}p {ping}`, or with
+/// [`StatusField`]s a line summing them all up.
+#[derive(Debug, Clone)]
+pub struct Template<F = Field>(Vec<Piece<F>>);
+
+#[derive(Debug, Clone)]
+enum Piece<F> {
+    Text(String),
+    Field(F),
+}
+
+/// What a template can fill in, and by what name.
+pub trait Placeholder: Copy + 'static {
+    const ALL: &'static [(&'static str, Self)];
+}
+
+/// What's filled in about each room.
+#[derive(Debug, Clone, Copy)]
+pub enum Field {
+    Name,
+    Game,
+    Address,
+    Host,
+    Port,
+    Players,
+    Ping,
+    Loss,
+    Locked,
+}
+
+impl Placeholder for Field {
+    const ALL: &'static [(&'static str, Self)] = &[
+        ("name", Field::Name),
+        ("game", Field::Game),
+        ("address", Field::Address),
+        ("host", Field::Host),
+        ("port", Field::Port),
+        ("players", Field::Players),
+        ("ping", Field::Ping),
+        ("loss", Field::Loss),
+        ("locked", Field::Locked),
+    ];
+}
+
+/// What's filled in about all of the rooms, for --statusline.
+#[derive(Debug, Clone, Copy)]
+pub enum StatusField {
+    Game,
+    Rooms,
+    Reachable,
+    Players,
+    Best,
+    BestName,
+}
+
+impl Placeholder for StatusField {
+    const ALL: &'static [(&'static str, Self)] = &[
+        ("game", StatusField::Game),
+        ("rooms", StatusField::Rooms),
+        ("reachable", StatusField::Reachable),
+        ("players", StatusField::Players),
+        ("best", StatusField::Best),
+        ("best_name", StatusField::BestName),
+    ];
+}
+
+/// The rooms summed up, for a [`Template<StatusField>`].
+pub struct Status<'a> {
+    pub games: &'a [String],
+    pub rooms: usize,
+    pub reachable: usize,
+    pub players: usize,
+    /// The room with the lowest ping, and that ping
+    pub best: Option<(&'a Room, Duration)>,
+}
+
+impl<F: Placeholder> Template<F> {
+    /// Parse `template`, where `{{` and `}}` stand for literal braces.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek() == Some(&c) => {
+                    chars.next();
+                    text.push(c);
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("{{{name} is never closed with }}")),
+                        }
+                    }
+
+                    let Some(&(_, field)) = F::ALL.iter().find(|(known, _)| *known == name) else {
+                        let known = F::ALL.iter().map(|(known, _)| format!("{{{known}}}"));
+                        let known = known.collect::<Vec<_>>().join(" ");
+                        return Err(format!(
+                            "unknown placeholder {{{name}}}, expected one of {known}"
+                        ));
+                    };
+
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err("unmatched }, write }} for a literal one".to_string()),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template(pieces))
+    }
+}
+
+impl Template {
+    pub fn render(&self, room: &Room, options: &PlainOptions) -> String {
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(Field::Name) => name(room, options),
+                Piece::Field(Field::Game) => clean(&room.game_name).into_owned(),
+                Piece::Field(Field::Address) => room.address.clone(),
+                Piece::Field(Field::Host) => host(room),
+                Piece::Field(Field::Port) => room.port.to_string(),
+                Piece::Field(Field::Players) => room.players.len().to_string(),
+                Piece::Field(Field::Ping) => {
+                    let min = room.ping.stats().map(|ping| ping.min);
+                    let text = format_ping(&room.ping);
+                    match &options.colors {
+                        Some(colors) => colors.paint(min, &text),
+                        None => text,
+                    }
+                }
+                Piece::Field(Field::Loss) => room.packet_loss.map(format_loss).unwrap_or_default(),
+                Piece::Field(Field::Locked) => {
+                    if room.has_password {
+                        "locked".to_string()
+                    } else {
+                        String::new()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl Template<StatusField> {
+    /// What --statusline prints by default.
+    pub const DEFAULT: &'static str = "{game}: {rooms} rooms, best {best}";
+
+    pub fn render_status(&self, status: &Status) -> String {
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(StatusField::Game) => match status.games {
+                    [] => "every game".to_string(),
+                    games => clean(&games.join(", ")).into_owned(),
+                },
+                Piece::Field(StatusField::Rooms) => status.rooms.to_string(),
+                Piece::Field(StatusField::Reachable) => status.reachable.to_string(),
+                Piece::Field(StatusField::Players) => status.players.to_string(),
+                Piece::Field(StatusField::Best) => status
+                    .best
+                    .map_or("-".to_string(), |(_, ping)| format_latency(ping)),
+                Piece::Field(StatusField::BestName) => status
+                    .best
+                    .map_or("-".to_string(), |(room, _)| clean(&room.name).into_owned()),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+//! Rooms whose hostname resolved to several addresses, pinged with `all_addresses`.
+
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use yuzuping::{ping_rooms, Error, PingConfig, Pinger, Replies, Room};
+
+/// Replies quicker the higher the last byte of the address is, and not at all from
+/// 10.0.0.0.
+struct ByAddress;
+
+impl Pinger for ByAddress {
+    async fn ping(&self, ip: &IpAddr, _: u16) -> Result<Replies, Error> {
+        let IpAddr::V4(ip) = ip else {
+            return Ok(Replies::from_probes(Vec::new(), 1));
+        };
+        let latencies = match ip.octets()[3] {
+            0 => Vec::new(),
+            last => vec![Duration::from_millis(100 - u64::from(last))],
+        };
+        Ok(Replies::from_probes(latencies, 1))
+    }
+}
+
+#[tokio::test]
+async fn keeps_the_quickest() {
+    let ips =
+        ["10.0.0.10", "10.0.0.0", "10.0.0.60", "10.0.0.30"].map(|ip| ip.parse::<IpAddr>().unwrap());
+    let mut rooms = vec![Room {
+        name: "Everywhere".to_string(),
+        address: "rooms.example".to_string(),
+        port: 24872,
+        ip: Some(ips[0]),
+        other_ips: ips[1..].to_vec(),
+        ..Room::default()
+    }];
+
+    let config = PingConfig::builder().all_addresses(true).build();
+    ping_rooms(
+        &mut rooms,
+        &config,
+        &ByAddress,
+        &CancellationToken::new(),
+        |_, _, _| {},
+    )
+    .await
+    .unwrap();
+
+    let room = &rooms[0];
+    assert_eq!(room.ip, Some(ips[2]));
+    assert_eq!(room.other_ips.len(), 3);
+    assert!(!room.other_ips.contains(&ips[2]));
+    let stats = room.ping.stats().unwrap();
+    assert_eq!(stats.min, Duration::from_millis(40));
+}
@@ -0,0 +1,87 @@
+//! Cancelling `ping_rooms` part way through, the way ctrl-c and --stop-after do, and
+//! dropping `ping_rooms_stream` before it's done.
+
+mod common;
+
+use futures::StreamExt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use yuzuping::{ping_rooms, ping_rooms_stream, Error, PingConfig, Pinger, ProbeResult, Replies};
+
+/// Replies straight away to the rooms on an even port, and never to the rest, so that
+/// they're only ever done with by cancelling.
+struct Hanging;
+
+impl Pinger for Hanging {
+    async fn ping(&self, _: &IpAddr, port: u16) -> Result<Replies, Error> {
+        if port % 2 == 1 {
+            std::future::pending::<()>().await;
+        }
+        Ok(Replies::from_probes(vec![Duration::from_millis(5)], 1))
+    }
+}
+
+/// Like [`Hanging`], keeping count of the pings that are still running.
+#[derive(Default)]
+struct Counting {
+    running: AtomicUsize,
+}
+
+/// Counts a ping as done once it's dropped, whether it finished or not.
+struct Running<'a>(&'a AtomicUsize);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Pinger for Counting {
+    async fn ping(&self, ip: &IpAddr, port: u16) -> Result<Replies, Error> {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        let _running = Running(&self.running);
+        Hanging.ping(ip, port).await
+    }
+}
+
+#[tokio::test]
+async fn stops_part_way() {
+    let mut rooms = common::rooms(20);
+
+    // cancelled once every room that replies did
+    let cancel = CancellationToken::new();
+    let config = PingConfig::builder().concurrency(20).build();
+    ping_rooms(&mut rooms, &config, &Hanging, &cancel, |_, done, _| {
+        if done == 10 {
+            cancel.cancel();
+        }
+    })
+    .await
+    .unwrap();
+
+    for room in &rooms {
+        if room.port % 2 == 0 {
+            assert!(room.ping.is_reachable(), "{} wasn't pinged", room.name);
+        } else {
+            assert_eq!(room.ping, ProbeResult::Pending, "{} was pinged", room.name);
+        }
+    }
+}
+
+#[tokio::test]
+async fn dropping_the_stream_stops_the_pings() {
+    let pinger = Counting::default();
+    let config = PingConfig::builder().concurrency(20).build();
+    let mut stream = Box::pin(ping_rooms_stream(common::rooms(20), &config, &pinger));
+
+    // only the rooms on an even port ever reply, the rest are left hanging
+    let pinged = stream.by_ref().take(10).collect::<Vec<_>>().await;
+    assert!(pinged.iter().all(|room| room.port % 2 == 0));
+    assert!(pinged.iter().all(|room| room.ping.is_reachable()));
+    assert!(pinger.running.load(Ordering::SeqCst) > 0);
+
+    drop(stream);
+    assert_eq!(pinger.running.load(Ordering::SeqCst), 0);
+}
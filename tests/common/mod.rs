@@ -0,0 +1,15 @@
+//! What the tests pinging rooms have in common.
+
+use yuzuping::Room;
+
+/// `count` rooms on localhost, each on a port of its own counting up from 24872.
+pub fn rooms(count: u32) -> Vec<Room> {
+    (0..count)
+        .map(|i| Room {
+            name: format!("Room {i}"),
+            address: "127.0.0.1".to_string(),
+            port: 24872 + i,
+            ..Room::default()
+        })
+        .collect()
+}
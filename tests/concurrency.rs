@@ -0,0 +1,49 @@
+//! How many rooms `ping_rooms` pings at once with an adaptive concurrency.
+
+mod common;
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use yuzuping::{ping_rooms, Error, PingConfig, Pinger, Replies};
+
+/// Replies after the same while every time, keeping track of how many pings were in
+/// flight at once. The clock is paused, so that while is the same however loaded the
+/// machine is.
+#[derive(Default)]
+struct Steady {
+    running: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl Pinger for Steady {
+    async fn ping(&self, _: &IpAddr, _: u16) -> Result<Replies, Error> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(Replies::from_probes(vec![Duration::from_millis(5)], 1))
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn ramps_up_while_the_pings_keep_up() {
+    let mut rooms = common::rooms(100);
+
+    let pinger = Steady::default();
+    let config = PingConfig::builder().concurrency(16).adaptive(true).build();
+    ping_rooms(
+        &mut rooms,
+        &config,
+        &pinger,
+        &CancellationToken::new(),
+        |_, _, _| {},
+    )
+    .await
+    .unwrap();
+
+    assert!(rooms.iter().all(|room| room.ping.is_reachable()));
+    let peak = pinger.peak.load(Ordering::SeqCst);
+    assert!(4 < peak && peak <= 16, "{peak} pings at once");
+}
@@ -0,0 +1,156 @@
+//! Fetching from a lobby served on localhost, which answers every request the same.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use yuzuping::{fetch_rooms, Error, PingConfig};
+
+const LOBBY: &str = r#"{"rooms": [
+    {"port": 24872, "name": "Smash", "preferredGameName": "Super Smash Bros. Ultimate",
+     "address": "203.0.113.1", "players": [{"nickname": "Alice", "gameName": "Super Smash Bros. Ultimate"}],
+     "maxPlayers": 4, "hasPassword": false},
+    {"port": 24872, "name": "Kart", "preferredGameName": "Mario Kart 8 Deluxe",
+     "address": "203.0.113.2", "players": [], "maxPlayers": 8, "hasPassword": true},
+    {"port": 24873, "name": "More smash", "preferredGameName": "Super Smash Bros. Ultimate",
+     "address": "203.0.113.3", "players": [], "maxPlayers": 4, "hasPassword": true}
+]}"#;
+
+/// Serve `body` with `status` to every request, returning the url to fetch it from.
+async fn serve(status: &'static str, body: &'static str) -> String {
+    serve_encoded(status, None, body.as_bytes().to_vec()).await
+}
+
+/// Like [`serve`], with a `body` compressed with `encoding`.
+async fn serve_encoded(
+    status: &'static str,
+    encoding: Option<&'static str>,
+    body: Vec<u8>,
+) -> String {
+    let encoding = match encoding {
+        Some(encoding) => format!("Content-Encoding: {encoding}\r\n"),
+        None => String::new(),
+    };
+    let mut response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         {encoding}\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend(body);
+    let response = Arc::<[u8]>::from(response);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let response = response.clone();
+            tokio::spawn(async move {
+                // the requests are small enough to arrive in one read
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).await;
+                let _ = stream.write_all(&response).await;
+            });
+        }
+    });
+
+    format!("http://{addr}/lobby")
+}
+
+fn smash() -> PingConfig {
+    PingConfig::builder()
+        .game("Super Smash Bros. Ultimate")
+        .build()
+}
+
+#[tokio::test]
+async fn keeps_only_the_game() {
+    let url = serve("200 OK", LOBBY).await;
+    let rooms = fetch_rooms(&reqwest::Client::new(), &url, &smash())
+        .await
+        .unwrap();
+
+    let names = rooms
+        .iter()
+        .map(|room| room.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Smash", "More smash"]);
+    assert_eq!(rooms[0].players[0].nickname, "Alice");
+    assert!(rooms[1].has_password);
+}
+
+#[tokio::test]
+async fn gzipped() {
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(LOBBY.as_bytes()).unwrap();
+    let url = serve_encoded("200 OK", Some("gzip"), gzip.finish().unwrap()).await;
+    let rooms = fetch_rooms(&reqwest::Client::new(), &url, &smash())
+        .await
+        .unwrap();
+
+    assert_eq!(rooms.len(), 2);
+}
+
+#[tokio::test]
+async fn every_game_without_one() {
+    let url = serve("200 OK", LOBBY).await;
+    let config = PingConfig::builder().build();
+    let rooms = fetch_rooms(&reqwest::Client::new(), &url, &config)
+        .await
+        .unwrap();
+
+    assert_eq!(rooms.len(), 3);
+}
+
+#[tokio::test]
+async fn empty_lobby() {
+    let url = serve("200 OK", r#"{"rooms": []}"#).await;
+    let rooms = fetch_rooms(&reqwest::Client::new(), &url, &smash())
+        .await
+        .unwrap();
+
+    assert!(rooms.is_empty());
+}
+
+#[tokio::test]
+async fn http_errors() {
+    let url = serve("503 Service Unavailable", "down for maintenance").await;
+    let err = fetch_rooms(&reqwest::Client::new(), &url, &smash())
+        .await
+        .unwrap_err();
+
+    match err {
+        Error::Http(err) => {
+            assert_eq!(err.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE))
+        }
+        err => panic!("expected an http error, got {err:?}"),
+    }
+}
+
+#[tokio::test]
+async fn not_a_lobby() {
+    let url = serve("200 OK", "<html>not json</html>").await;
+    let err = fetch_rooms(&reqwest::Client::new(), &url, &smash())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, Error::Decode(_)), "{err:?}");
+    assert!(!err.is_unfamiliar_schema());
+}
+
+#[tokio::test]
+async fn unfamiliar_lobby() {
+    let url = serve("200 OK", r#"{"version": 2, "lobbies": []}"#).await;
+    let err = fetch_rooms(&reqwest::Client::new(), &url, &smash())
+        .await
+        .unwrap_err();
+
+    assert!(err.is_unfamiliar_schema(), "{err:?}");
+}
@@ -0,0 +1,79 @@
+//! Tidying up the rooms of a lobby before they're pinged.
+
+use std::net::IpAddr;
+use yuzuping::{resolve_rooms, PingConfig, Player, ProbeResult, Response, Room};
+
+fn room(name: &str, address: &str, port: u32, players: usize) -> Room {
+    Room {
+        name: name.to_string(),
+        address: address.to_string(),
+        port,
+        players: (0..players)
+            .map(|i| Player {
+                nickname: format!("player{i}"),
+                game: String::new(),
+            })
+            .collect(),
+        ..Room::default()
+    }
+}
+
+#[test]
+fn dedup_keeps_the_fullest() {
+    let mut resp = Response {
+        rooms: vec![
+            room("First", "10.0.0.1", 24872, 1),
+            room("Other port", "10.0.0.1", 24873, 0),
+            room("Other host", "10.0.0.2", 24872, 0),
+            room("First again", "10.0.0.1", 24872, 3),
+            room("First once more", "10.0.0.1", 24872, 2),
+        ],
+    };
+    resp.dedup();
+
+    let names = resp
+        .rooms
+        .iter()
+        .map(|room| room.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["First again", "Other port", "Other host"]);
+}
+
+#[tokio::test]
+async fn addresses_are_cleaned_up() {
+    let mut rooms = vec![
+        room("Padded", "  203.0.113.5 \t", 24872, 0),
+        room("Scheme and port", "tcp://203.0.113.6:30000/", 24872, 0),
+        room("Bracketed", "[2001:db8::1]:24873", 24872, 0),
+        room("Bare v6", "2001:db8::2", 24872, 0),
+        room("Option", "-f 203.0.113.7", 24872, 0),
+        room("Bad port", "203.0.113.8:lots", 24872, 0),
+    ];
+    resolve_rooms(&mut rooms, &PingConfig::builder().build()).await;
+
+    let ip = |ip: &str| Some(ip.parse::<IpAddr>().unwrap());
+    let resolved = rooms
+        .iter()
+        .map(|room| (room.ip, room.port))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        resolved,
+        [
+            (ip("203.0.113.5"), 24872),
+            (ip("203.0.113.6"), 30000),
+            (ip("2001:db8::1"), 24873),
+            (ip("2001:db8::2"), 24872),
+            (None, 24872),
+            (None, 24872),
+        ]
+    );
+    // the garbage is never handed to ping, and says why
+    for room in &rooms[4..] {
+        assert!(
+            matches!(room.ping, ProbeResult::Error(_)),
+            "{}: {:?}",
+            room.name,
+            room.ping
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Reading what the system `ping` printed, from transcripts of the pings of each system.
+
+use std::net::IpAddr;
+use std::time::Duration;
+use yuzuping::{Replies, Summary};
+
+const WINDOWS: &str = "
+Pinging 10.0.0.1 with 32 bytes of data:
+Reply from 10.0.0.1: bytes=32 time=12ms TTL=57
+Reply from 10.0.0.1: bytes=32 time<1ms TTL=57
+Request timed out.
+
+Ping statistics for 10.0.0.1:
+    Packets: Sent = 3, Received = 2, Lost = 1 (33% loss),
+Approximate round trip times in milli-seconds:
+    Minimum = 0ms, Maximum = 12ms, Average = 6ms
+";
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+fn ms(ms: f64) -> Duration {
+    Duration::from_secs_f64(ms / 1000.0)
+}
+
+#[test]
+fn linux_keeps_fractions() {
+    let stdout = "\
+PING 192.168.1.20 (192.168.1.20) 56(84) bytes of data.
+64 bytes from 192.168.1.20: icmp_seq=1 ttl=64 time=0.412 ms
+64 bytes from 192.168.1.20: icmp_seq=2 ttl=64 time=0.387 ms
+64 bytes from 192.168.1.20: icmp_seq=3 ttl=64 time=12.3 ms
+
+--- 192.168.1.20 ping statistics ---
+3 packets transmitted, 3 received, 0% packet loss, time 2031ms
+rtt min/avg/max/mdev = 0.387/4.366/12.300/5.604 ms
+";
+    let replies = Replies::from_ping_output(&ip("192.168.1.20"), stdout.as_bytes(), 3);
+
+    assert_eq!(replies.latencies, [ms(0.412), ms(0.387), ms(12.3)]);
+    assert_eq!(replies.packet_loss, Some(0.0));
+}
+
+#[test]
+fn macos() {
+    let stdout = "\
+PING 203.0.113.7 (203.0.113.7): 56 data bytes
+64 bytes from 203.0.113.7: icmp_seq=0 ttl=54 time=12.412 ms
+64 bytes from 203.0.113.7: icmp_seq=1 ttl=54 time=11.903 ms
+Request timeout for icmp_seq 2
+
+--- 203.0.113.7 ping statistics ---
+3 packets transmitted, 2 packets received, 33.3% packet loss
+round-trip min/avg/max/stddev = 11.903/12.158/12.412/0.255 ms
+";
+    let replies = Replies::from_ping_output(&ip("203.0.113.7"), stdout.as_bytes(), 3);
+
+    assert_eq!(replies.latencies, [ms(12.412), ms(11.903)]);
+    assert_eq!(replies.packet_loss, Some(33.3));
+}
+
+#[test]
+fn busybox_without_reply_times() {
+    let stdout = "\
+PING 10.0.0.9 (10.0.0.9): 56 data bytes
+64 bytes from 10.0.0.9: seq=0 ttl=64
+64 bytes from 10.0.0.9: seq=1 ttl=64
+64 bytes from 10.0.0.9: seq=2 ttl=64
+
+--- 10.0.0.9 ping statistics ---
+3 packets transmitted, 3 packets received, 0% packet loss
+round-trip min/avg/max = 1.104/2.250/3.301 ms
+";
+    let replies = Replies::from_ping_output(&ip("10.0.0.9"), stdout.as_bytes(), 3);
+
+    // the average stands in for the replies
+    assert_eq!(replies.latencies, [ms(2.25)]);
+    assert_eq!(replies.packet_loss, Some(0.0));
+}
+
+#[test]
+fn ipv6() {
+    let stdout = "\
+PING 2001:db8::1(2001:db8::1) 56 data bytes
+64 bytes from 2001:db8::1: icmp_seq=1 ttl=57 time=20.1 ms
+64 bytes from 2001:db8::1: icmp_seq=2 ttl=57 time=19.8 ms
+
+--- 2001:db8::1 ping statistics ---
+2 packets transmitted, 2 received, 0% packet loss, time 1001ms
+rtt min/avg/max/mdev = 19.800/19.950/20.100/0.150 ms
+";
+    // ping echoes the address back the short way, however the lobby wrote it
+    let replies = Replies::from_ping_output(&ip("2001:0db8:0:0::0001"), stdout.as_bytes(), 2);
+
+    assert_eq!(replies.latencies, [ms(20.1), ms(19.8)]);
+}
+
+#[test]
+fn only_the_whole_address() {
+    let stdout = "\
+PING 11.0.0.1 (11.0.0.1) 56(84) bytes of data.
+64 bytes from 11.0.0.1: icmp_seq=1 ttl=52 time=31.0 ms
+64 bytes from 1.0.0.1: icmp_seq=1 ttl=52 time=8.00 ms
+
+--- 11.0.0.1 ping statistics ---
+1 packets transmitted, 1 received, 0% packet loss, time 0ms
+";
+    let eleven = Replies::from_ping_output(&ip("11.0.0.1"), stdout.as_bytes(), 1);
+    let one = Replies::from_ping_output(&ip("1.0.0.1"), stdout.as_bytes(), 1);
+
+    assert_eq!(eleven.latencies, [ms(31.0)]);
+    assert_eq!(one.latencies, [ms(8.0)]);
+}
+
+#[test]
+fn windows_under_a_millisecond() {
+    let replies = Replies::from_ping_output(&ip("10.0.0.1"), WINDOWS.as_bytes(), 3);
+
+    // `time<1ms` is somewhere under a millisecond, so it's taken as half of one
+    assert_eq!(
+        replies.latencies,
+        [Duration::from_millis(12), Duration::from_micros(500)]
+    );
+}
+
+#[test]
+fn windows_statistics() {
+    let stdout = "
+Pinging 10.0.0.1 with 32 bytes of data:
+Reply from 10.0.0.1: bytes=32 time=10ms TTL=57
+Reply from 10.0.0.1: bytes=32 time=40ms TTL=57
+Reply from 10.0.0.1: bytes=32 time=12ms TTL=57
+
+Ping statistics for 10.0.0.1:
+    Packets: Sent = 3, Received = 3, Lost = 0 (0% loss),
+Approximate round trip times in milli-seconds:
+    Minimum = 10ms, Maximum = 40ms, Average = 20ms
+";
+    let summary = Some(Summary {
+        min: Duration::from_millis(10),
+        avg: Duration::from_millis(20),
+        max: Duration::from_millis(40),
+    });
+    let replies = Replies::from_ping_output(&ip("10.0.0.1"), stdout.as_bytes(), 3);
+    assert_eq!(replies.summary, summary);
+    assert_eq!(replies.packet_loss, Some(0.0));
+
+    // without replies to go by, the average stands in for them
+    let stats_only = stdout
+        .lines()
+        .filter(|line| !line.starts_with("Reply"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let replies = Replies::from_ping_output(&ip("10.0.0.1"), stats_only.as_bytes(), 3);
+    assert_eq!(replies.summary, summary);
+    assert_eq!(replies.latencies, [Duration::from_millis(20)]);
+}
+
+#[test]
+fn windows_unreachable() {
+    let stdout = "
+Pinging 10.0.0.1 with 32 bytes of data:
+Reply from 10.0.0.1: bytes=32 time=14ms TTL=57
+Reply from 10.0.0.1: Destination host unreachable.
+Reply from 10.0.0.1: bytes=32 time=15ms TTL=57
+
+Ping statistics for 10.0.0.1:
+    Packets: Sent = 3, Received = 3, Lost = 0 (0% loss),
+Approximate round trip times in milli-seconds:
+    Minimum = 14ms, Maximum = 15ms, Average = 14ms
+";
+    let replies = Replies::from_ping_output(&ip("10.0.0.1"), stdout.as_bytes(), 3);
+
+    assert_eq!(
+        replies.latencies,
+        [Duration::from_millis(14), Duration::from_millis(15)]
+    );
+    assert!(replies.unreachable);
+    // windows counts the unreachable reply as received, but it's as good as lost
+    let loss = replies.packet_loss.unwrap();
+    assert!((loss - 33.3).abs() < 0.1, "{loss}% loss");
+}
+
+#[test]
+fn german_windows() {
+    let stdout = "
+Ping wird ausgeführt für 10.0.0.1 mit 32 Bytes Daten:
+Antwort von 10.0.0.1: Bytes=32 Zeit=12ms TTL=57
+Antwort von 10.0.0.1: Bytes=32 Zeit<1ms TTL=57
+
+Ping-Statistik für 10.0.0.1:
+    Pakete: Gesendet = 2, Empfangen = 2, Verloren = 0
+    (0% Verlust),
+Ca. Zeitangaben in Millisek.:
+    Minimum = 0ms, Maximum = 12ms, Mittelwert = 6ms
+";
+    let replies = Replies::from_ping_output(&ip("10.0.0.1"), stdout.as_bytes(), 2);
+
+    assert_eq!(
+        replies.latencies,
+        [Duration::from_millis(12), Duration::from_micros(500)]
+    );
+}
+
+#[test]
+fn french_windows() {
+    let stdout = "
+Envoi d’une requête 'Ping'  10.0.0.1 avec 32 octets de données :
+Réponse de 10.0.0.1 : octets=32 temps=12 ms TTL=57
+Réponse de 10.0.0.1 : octets=32 temps<1ms TTL=57
+
+Statistiques Ping pour 10.0.0.1:
+    Paquets : envoyés = 2, reçus = 2, perdus = 0 (perte 0%),
+Durée approximative des boucles en millisecondes :
+    Minimum = 0ms, Maximum = 12ms, Moyenne = 6ms
+";
+    let replies = Replies::from_ping_output(&ip("10.0.0.1"), stdout.as_bytes(), 2);
+
+    assert_eq!(
+        replies.latencies,
+        [Duration::from_millis(12), Duration::from_micros(500)]
+    );
+}
+
+#[test]
+fn crlf_reads_like_lf() {
+    let ip = ip("10.0.0.1");
+    let crlf = WINDOWS.replace('\n', "\r\n");
+    let replies = Replies::from_ping_output(&ip, crlf.as_bytes(), 3);
+
+    assert_eq!(
+        replies,
+        Replies::from_ping_output(&ip, WINDOWS.as_bytes(), 3)
+    );
+    assert_eq!(
+        replies.latencies,
+        [Duration::from_millis(12), Duration::from_micros(500)]
+    );
+    assert_eq!(replies.packet_loss, Some(33.0));
+    assert_eq!(
+        replies.summary,
+        Some(Summary {
+            min: Duration::ZERO,
+            avg: Duration::from_millis(6),
+            max: Duration::from_millis(12),
+        })
+    );
+}
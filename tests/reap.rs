@@ -0,0 +1,114 @@
+//! A `--ping-cmd` wrapper that starts a ping of its own, and is given up on, whether by
+//! dropping it or once it's past its deadline.
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use yuzuping::{ping_rooms, PingCommand, PingConfig, Pinger, ProbeResult, Room, SubprocessPinger};
+
+/// Whether `pid` is still running. A zombie is as good as gone, whoever gets to reap it.
+fn running(pid: &str) -> bool {
+    match std::fs::read_to_string(Path::new("/proc").join(pid).join("stat")) {
+        Ok(stat) => !stat
+            .rsplit(')')
+            .next()
+            .unwrap_or_default()
+            .trim_start()
+            .starts_with('Z'),
+        Err(_) => false,
+    }
+}
+
+/// The pids of the wrapper and of the ping it started, once it wrote them to `path`.
+async fn written_pids(path: &Path) -> Vec<String> {
+    loop {
+        // written in one go, but it might be read part way
+        if let Ok(pids) = std::fs::read_to_string(path) {
+            if pids.ends_with('\n') {
+                return pids.split_whitespace().map(str::to_string).collect();
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Give the killed `pids` time to go away, however long a loaded machine takes.
+async fn wait_until_gone(pids: &[String]) {
+    for _ in 0..500 {
+        if !pids.iter().any(|pid| running(pid)) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// A `--ping-cmd` that starts a ping which never finishes, and writes its own pid and
+/// the ping's to the `pids` file next to it. Each test gets a directory of its own.
+fn wrapper(test: &str) -> (PathBuf, PathBuf, PingCommand) {
+    let dir = std::env::temp_dir().join(format!("yuzuping-{test}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let pids = dir.join("pids");
+    let wrapper = dir.join("wrapper.sh");
+    std::fs::write(
+        &wrapper,
+        format!("sleep 30 &\necho $$ $! > {}\nwait\n", pids.display()),
+    )
+    .unwrap();
+
+    let command = PingCommand::parse(&format!("sh {} {{address}}", wrapper.display())).unwrap();
+    (dir, pids, command)
+}
+
+#[tokio::test]
+async fn kills_what_the_wrapper_started() {
+    let (dir, pids, command) = wrapper("reap");
+    // a timeout long enough that the ping is only ever done with by dropping it
+    let pinger = SubprocessPinger::new(1, Duration::from_secs(60)).with_command(command);
+    let ip = "127.0.0.1".parse().unwrap();
+    let ping = pinger.ping(&ip, 24872);
+    let pids = tokio::select! {
+        _ = ping => panic!("the ping finished on its own"),
+        pids = written_pids(&pids) => pids,
+    };
+    // the ping was dropped along with the select, like on ctrl-c
+    assert_eq!(pids.len(), 2);
+    wait_until_gone(&pids).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    for pid in &pids {
+        assert!(!running(pid), "{pid} is still running");
+    }
+}
+
+#[tokio::test]
+async fn kills_it_past_the_deadline() {
+    let (dir, pids, command) = wrapper("deadline");
+    // given up on after a second and the timeout, for its one probe
+    let pinger = SubprocessPinger::new(1, Duration::from_millis(100)).with_command(command);
+    let mut rooms = vec![Room {
+        name: "Hangs".to_string(),
+        address: "127.0.0.1".to_string(),
+        port: 24872,
+        ..Room::default()
+    }];
+
+    let config = PingConfig::builder().retries(0).build();
+    ping_rooms(
+        &mut rooms,
+        &config,
+        &pinger,
+        &CancellationToken::new(),
+        |_, _, _| {},
+    )
+    .await
+    .unwrap();
+    assert_eq!(rooms[0].ping, ProbeResult::Timeout);
+
+    let pids = written_pids(&pids).await;
+    assert_eq!(pids.len(), 2);
+    wait_until_gone(&pids).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    for pid in &pids {
+        assert!(!running(pid), "{pid} is still running");
+    }
+}